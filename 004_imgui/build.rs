@@ -0,0 +1,49 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// rsc/shader以下のファイルを全てバイナリに埋め込む
+// OUT_DIR/shaders.rsに(rsc/shaderからの相対パス, include_str!)の一覧を書き出し、
+// engine::embed_shaders!()でその一覧を読み込む
+fn main() -> io::Result<()> {
+    let shader_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("rsc/shader");
+    // ディレクトリを指定すると、中のファイルの追加や変更でも作り直される
+    println!("cargo:rerun-if-changed=rsc/shader");
+
+    let mut files = Vec::new();
+    collect_files(&shader_dir, &mut files)?;
+    files.sort();
+
+    let mut table = String::from("&[\n");
+    for path in &files {
+        // 埋め込んだファイルの名前は、Windowsでも/で区切る
+        let name: Vec<String> = path
+            .strip_prefix(&shader_dir)
+            .unwrap()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        table.push_str(&format!(
+            "    ({:?}, include_str!({:?})),\n",
+            name.join("/"),
+            path.to_string_lossy()
+        ));
+    }
+    table.push_str("]\n");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("shaders.rs"), table)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -10,11 +10,7 @@ use imgui::im_str;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
-mod shader;
-mod vertex;
-
-use shader::Shader;
-use vertex::Vertex;
+use engine::{embed_shaders, EmbeddedShaders, Shader, Vertex};
 
 #[allow(dead_code)]
 type Point3 = cgmath::Point3<f32>;
@@ -28,6 +24,8 @@ const WINDOW_HEIGHT: u32 = 480;
 const FLOAT_NUM: usize = 3;
 const VERTEX_NUM: usize = 3;
 const BUF_LEN: usize = FLOAT_NUM * VERTEX_NUM;
+// シェーダーはバイナリに埋め込むので、どのディレクトリから起動してもよい
+const SHADERS: EmbeddedShaders = embed_shaders!();
 
 fn main() {
     let sdl_context = sdl2::init().unwrap();
@@ -51,7 +49,7 @@ fn main() {
     let _gl_context = window.gl_create_context().unwrap();
     gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as _);
 
-    let shader = Shader::new("rsc/shader/shader.vs", "rsc/shader/shader.fs");
+    let shader = Shader::from_embedded(SHADERS, "shader.vs", "shader.fs").unwrap();
 
     // set buffer
     #[rustfmt::skip]
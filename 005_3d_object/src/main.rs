@@ -10,11 +10,7 @@ use imgui::im_str;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
-mod shader;
-mod vertex;
-
-use shader::Shader;
-use vertex::Vertex;
+use engine::{embed_shaders, EmbeddedShaders, Shader, Vertex};
 
 #[allow(dead_code)]
 type Point3 = cgmath::Point3<f32>;
@@ -30,6 +26,8 @@ const FLOAT_NUM: usize = 3;
 // 三角形の頂点の数 = 三角形の頂点数 * 一面に使用する三角形の数 * 六面体 = 36
 const VERTEX_NUM: usize = 3 * 2 * 6;
 const BUF_LEN: usize = FLOAT_NUM * VERTEX_NUM;
+// シェーダーはバイナリに埋め込むので、どのディレクトリから起動してもよい
+const SHADERS: EmbeddedShaders = embed_shaders!();
 
 fn main() {
     let sdl_context = sdl2::init().unwrap();
@@ -53,7 +51,7 @@ fn main() {
     let _gl_context = window.gl_create_context().unwrap();
     gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as _);
 
-    let shader = Shader::new("rsc/shader/shader.vs", "rsc/shader/shader.fs");
+    let shader = Shader::from_embedded(SHADERS, "shader.vs", "shader.fs").unwrap();
 
     // set buffer
     #[rustfmt::skip]
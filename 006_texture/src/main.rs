@@ -11,13 +11,7 @@ use imgui::im_str;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
-mod image_manager;
-mod shader;
-mod vertex;
-
-use image_manager::ImageManager;
-use shader::Shader;
-use vertex::Vertex;
+use engine::{ImageManager, Shader, Vertex};
 
 #[allow(dead_code)]
 type Point3 = cgmath::Point3<f32>;
@@ -56,17 +50,19 @@ fn main() {
 
     // ! 画像の読み込み
     let mut image_manager = ImageManager::new();
-    image_manager.load_image(
-        // 画像ファイルパス
-        Path::new("rsc/image/surface.png"),
-        // ID
-        "surface",
-        // 上下反転 なぜいる？
-        //  →画像は左上、3D空間は左下にある
-        true
-    );
+    image_manager
+        .load_image(
+            // 画像ファイルパス
+            Path::new("rsc/image/surface.png"),
+            // ID
+            "surface",
+            // 上下反転 なぜいる？
+            //  →画像は左上、3D空間は左下にある
+            true,
+        )
+        .unwrap();
 
-    let shader = Shader::new("rsc/shader/shader.vs", "rsc/shader/shader.fs");
+    let shader = Shader::new("rsc/shader/shader.vs", "rsc/shader/shader.fs").unwrap();
 
     // set buffer
     #[rustfmt::skip]
@@ -183,7 +179,7 @@ fn main() {
         z: 0.2,
     };
 
-    let surface_texture_id = image_manager.get_texture_id("surface");
+    let surface_texture_id = image_manager.get_texture_id("surface").unwrap();
 
     let mut event_pump = sdl_context.event_pump().unwrap();
     'running: loop {
@@ -0,0 +1,5 @@
+layout(std140) uniform Camera {
+    mat4 uView;
+    mat4 uProjection;
+    vec3 uViewPosition;
+};
@@ -0,0 +1,30 @@
+struct Material {
+    vec3 specular;
+    float shininess;
+};
+
+struct Light {
+    vec3 direction;
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+vec3 directionalLight(Light light, Material material, vec3 color, vec3 normal, vec3 viewDir)
+{
+    // ambient
+    vec3 ambient = light.ambient * color;
+
+    // diffuse
+    vec3 norm = normalize(normal);
+    vec3 lightDir = normalize(-light.direction);
+    float diff = max(dot(norm, lightDir), 0.0);
+    vec3 diffuse = light.diffuse * diff * color;
+
+    // specular
+    vec3 reflectDir = reflect(-lightDir, norm);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
+    vec3 specular = light.specular * spec * material.specular;
+
+    return ambient + diffuse + specular;
+}
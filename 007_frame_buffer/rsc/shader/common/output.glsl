@@ -0,0 +1,8 @@
+// リニアな色を画面へ書き込む形にする
+// ウィンドウがsRGBに対応していてGL_FRAMEBUFFER_SRGBが有効なときは、uGammaを1.0にしてGPUに任せる
+uniform float uGamma;
+
+vec4 encodeOutput(vec3 color)
+{
+    return vec4(pow(max(color, vec3(0.0)), vec3(1.0 / uGamma)), 1.0);
+}
@@ -0,0 +1,4 @@
+in vec3 iPosition;
+in vec2 iTexCoords;
+
+out vec2 TexCoords;
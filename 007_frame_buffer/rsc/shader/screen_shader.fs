@@ -1,10 +1,12 @@
 #version 140
 
+#include "common/output.glsl"
+
 in vec2 TexCoords;
 
 uniform sampler2D uScreenTexture;
 
 void main()
 {
-    gl_FragColor = vec4(texture(uScreenTexture, TexCoords).rgb, 1.0);
+    gl_FragColor = encodeOutput(texture(uScreenTexture, TexCoords).rgb);
 }
@@ -1,9 +1,6 @@
 #version 140
 
-in vec3 iPosition;
-in vec2 iTexCoords;
-
-out vec2 TexCoords;
+#include "common/screen_vertex.glsl"
 
 void main()
 {
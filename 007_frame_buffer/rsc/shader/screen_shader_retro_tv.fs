@@ -1,5 +1,7 @@
 #version 140
 
+#include "common/output.glsl"
+
 in vec2 TexCoords;
 
 uniform sampler2D uScreenTexture;
@@ -32,5 +34,5 @@ void main()
 {
     float n1 = noise1(TexCoords.y*uScreenHeight);
     float n2 = noise2(TexCoords.y*uScreenHeight, uTime);
-    gl_FragColor = vec4(texture(uScreenTexture, TexCoords).rgb*n1*n2, 1.0);
+    gl_FragColor = encodeOutput(texture(uScreenTexture, TexCoords).rgb*n1*n2);
 }
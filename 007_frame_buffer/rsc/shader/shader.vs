@@ -1,12 +1,12 @@
 #version 140
 
+#include "common/camera.glsl"
+
 in vec3 iPosition;
 in vec3 iNormal;
 in vec2 iTexCoords;
 
 uniform mat4 uModel;
-uniform mat4 uView;
-uniform mat4 uProjection;
 uniform float uAlpha;
 
 out float Alpha;
@@ -14,28 +14,21 @@
          それをフレームバッファーオブジェクトにアタッチする
  */
 
-use std::mem;
-use std::os::raw::c_void;
 use std::path::Path;
 use std::time::Duration;
 
 use c_str_macro::c_str;
 use cgmath::perspective;
 use cgmath::prelude::SquareMatrix;
-use gl::types::{GLfloat, GLsizei, GLsizeiptr};
 use imgui::im_str;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
-mod frame_buffer;
-mod image_manager;
-mod shader;
-mod vertex;
-
-use frame_buffer::FrameBuffer;
-use image_manager::ImageManager;
-use shader::Shader;
-use vertex::Vertex;
+use engine::{
+    deduplicate_vertices, default_framebuffer_is_srgb, impl_std140, impl_vertex_layout,
+    new_screen_vertex_vec, Checkerboard, ColorSpace, Filter, FrameBuffer, ImageManager, LeakCheck,
+    MinFilter, ProgramCache, ShaderRegistry, Skybox, TextureOptions, UniformBuffer, Vertex, Wrap,
+};
 
 #[allow(dead_code)]
 type Point3 = cgmath::Point3<f32>;
@@ -49,6 +42,59 @@ const WINDOW_HEIGHT: u32 = 480;
 const FLOAT_NUM: usize = 8;
 const VERTEX_NUM: usize = 36;
 const BUF_LEN: usize = FLOAT_NUM * VERTEX_NUM;
+// ブルームの重み 中心からの距離ごとの値
+const BLOOM_RATIO: [f32; 6] = [0.398942, 0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216];
+
+// 立方体の頂点: 三次元座標、法線、テクスチャ上の二次元座標
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CubeVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+impl_vertex_layout!(CubeVertex {
+    position,
+    normal,
+    tex_coords
+});
+
+// std140ではvec3は16バイト境界に揃えられるので、後ろにパディングを入れる
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CameraBlock {
+    view: Matrix4,
+    projection: Matrix4,
+    view_position: Vector3,
+    _padding: f32,
+}
+
+impl_std140!(CameraBlock {
+    view: "uView",
+    projection: "uProjection",
+    view_position: "uViewPosition",
+});
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LightBlock {
+    direction: Vector3,
+    _padding0: f32,
+    ambient: Vector3,
+    _padding1: f32,
+    diffuse: Vector3,
+    _padding2: f32,
+    specular: Vector3,
+    _padding3: f32,
+}
+
+impl_std140!(LightBlock {
+    direction: "uLight.direction",
+    ambient: "uLight.ambient",
+    diffuse: "uLight.diffuse",
+    specular: "uLight.specular",
+});
 
 enum ShaderMode {
     General,
@@ -65,6 +111,8 @@ fn main() {
         let gl_attr = video_subsystem.gl_attr();
         gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
         gl_attr.set_context_version(3, 1);
+        // 最後のパスでリニアからsRGBへの変換をGPUに任せられるようにする
+        gl_attr.set_framebuffer_srgb_compatible(true);
         let (major, minor) = gl_attr.context_version();
         println!("OK: init OpenGL: version={}.{}", major, minor);
     }
@@ -78,53 +126,111 @@ fn main() {
 
     let _gl_context = window.gl_create_context().unwrap();
     gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as _);
+    // 終了時に、削除されずに残ったOpenGLのオブジェクトを表示する
+    let _leak_check = LeakCheck::new();
 
     let mut shader_mode = ShaderMode::General;
 
-    #[rustfmt::skip]
-    let screen_shader = Shader::new(
-        "rsc/shader/screen_shader.vs",
-        "rsc/shader/screen_shader.fs"
-    );
-    let screen_shader_sphere = Shader::new(
-        "rsc/shader/screen_shader_sphere.vs",
-        "rsc/shader/screen_shader_sphere.fs",
-    );
-    let screen_shader_bloom = Shader::new(
-        "rsc/shader/screen_shader_bloom.vs",
-        "rsc/shader/screen_shader_bloom.fs",
-    );
-    let screen_shader_retro_tv = Shader::new(
-        "rsc/shader/screen_shader_retro_tv.vs",
-        "rsc/shader/screen_shader_retro_tv.fs",
-    );
+    // rsc/shader以下のファイルを更新すると、実行中に再コンパイルされる
+    let mut shader_registry = ShaderRegistry::new();
+    // リンク済みのプログラムをtarget以下に保存して、2回目以降の起動を速くする
+    shader_registry.set_program_cache(Some(ProgramCache::new("target/shader_cache")));
+    shader_registry
+        .load(
+            "screen",
+            "rsc/shader/screen_shader.vs",
+            "rsc/shader/screen_shader.fs",
+        )
+        .unwrap();
+    shader_registry
+        .load(
+            "screen_sphere",
+            "rsc/shader/screen_shader_sphere.vs",
+            "rsc/shader/screen_shader_sphere.fs",
+        )
+        .unwrap();
+    shader_registry
+        .load(
+            "screen_bloom",
+            "rsc/shader/screen_shader_bloom.vs",
+            "rsc/shader/screen_shader_bloom.fs",
+        )
+        .unwrap();
+    shader_registry
+        .load(
+            "screen_retro_tv",
+            "rsc/shader/screen_shader_retro_tv.vs",
+            "rsc/shader/screen_shader_retro_tv.fs",
+        )
+        .unwrap();
 
     // フレームバッファーのインスタンス作成
     // ! フルスクリーンにすると元々のサイズ以上のところは黒くなっていたのはここが原因っぽい
-    let frame_buffer = FrameBuffer::new(WINDOW_WIDTH, WINDOW_HEIGHT);
+    // リニアな色のまま描くので、暗い部分の階調が潰れないように16ビットの浮動小数点数にする
+    let frame_buffer = FrameBuffer::with_format(
+        WINDOW_WIDTH,
+        WINDOW_HEIGHT,
+        gl::RGBA16F,
+        &TextureOptions::default(),
+    );
+
+    // ウィンドウがsRGBに対応していればGPUで変換し、そうでなければシェーダーでガンマ補正する
+    let srgb_output = default_framebuffer_is_srgb();
+    let output_gamma: f32 = if srgb_output { 1.0 } else { 2.2 };
+    println!("OK: sRGB output: framebuffer_srgb={}", srgb_output);
 
     let vertex_vec = new_screen_vertex_vec(-1.0, -1.0, 1.0, 1.0, 20);
 
     // 一つの頂点につき、三次元座標とテクスチャ上の二次元座標
-    let screen_vertex = Vertex::new(
-        (vertex_vec.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-        vertex_vec.as_ptr() as *const c_void,
-        gl::STATIC_DRAW,
-        vec![gl::FLOAT, gl::FLOAT],
-        vec![3, 2],
-        5 * mem::size_of::<GLfloat>() as GLsizei,
-        20 * 20 * 2 * 3,
-    );
+    // 隣り合う三角形で共有している頂点はインデックスでまとめる
+    let (screen_vertices, screen_indices) =
+        deduplicate_vertices::<_, u16>(&vertex_vec).expect("too many screen vertices");
+    let screen_vertex =
+        Vertex::from_indexed_vertices(&screen_vertices, &screen_indices, gl::STATIC_DRAW);
 
     let mut depth_test_frame: bool = true;
     let mut blend_frame: bool = true;
     let mut wireframe_frame: bool = false;
     let mut culling_frame: bool = true;
 
+    // 読み込めなかったテクスチャーは市松模様で表示する
     let mut image_manager = ImageManager::new();
-    image_manager.load_image(Path::new("rsc/image/surface.png"), "surface", true);
+    if let Err(error) = image_manager.set_fallback(Some(Checkerboard::default())) {
+        println!("{}", error);
+    }
+    // 斜めから見てもぼやけないように、ミップマップと異方性フィルタリングを使う
+    // 色の画像なのでsRGBとして読み込み、シェーダーではリニアな値で照明を計算する
+    let surface_options = TextureOptions::default()
+        .filter(MinFilter::LinearMipmapLinear, Filter::Linear)
+        .anisotropy(8.0)
+        .color_space(ColorSpace::Srgb);
+    if let Err(error) = image_manager.load_image_with_options(
+        Path::new("rsc/image/surface.png"),
+        "surface",
+        true,
+        &surface_options,
+    ) {
+        println!("{}", error);
+    }
+
+    // 背景のキューブマップ
+    // 読み込めなかった場合は、これまで通り白で塗りつぶす
+    let sky_options = TextureOptions::default()
+        .wrap(Wrap::ClampToEdge)
+        .color_space(ColorSpace::Srgb);
+    if let Err(error) = image_manager.load_equirectangular_cubemap(
+        Path::new("rsc/image/sky.png"),
+        "sky",
+        512,
+        &sky_options,
+    ) {
+        println!("{}", error);
+    }
+    let skybox = Skybox::new().unwrap();
 
-    let shader = Shader::new("rsc/shader/shader.vs", "rsc/shader/shader.fs");
+    shader_registry
+        .load("shader", "rsc/shader/shader.vs", "rsc/shader/shader.fs")
+        .unwrap();
 
     // set buffer
     #[rustfmt::skip]
@@ -184,15 +290,18 @@ fn main() {
         0.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 1.0,
     ];
 
-    let vertex = Vertex::new(
-        (BUF_LEN * mem::size_of::<GLfloat>()) as GLsizeiptr,
-        buffer_array.as_ptr() as *const c_void,
-        gl::STATIC_DRAW,
-        vec![gl::FLOAT, gl::FLOAT, gl::FLOAT],
-        vec![3, 3, 2],
-        8 * mem::size_of::<GLfloat>() as GLsizei,
-        VERTEX_NUM as i32,
-    );
+    // 面ごとに法線が異なるので、共有できるのは同じ面の2つの三角形の間の頂点だけ
+    let cube_vertex_vec: Vec<CubeVertex> = buffer_array
+        .chunks(FLOAT_NUM)
+        .map(|v| CubeVertex {
+            position: [v[0], v[1], v[2]],
+            normal: [v[3], v[4], v[5]],
+            tex_coords: [v[6], v[7]],
+        })
+        .collect();
+    let (cube_vertices, cube_indices) =
+        deduplicate_vertices::<_, u16>(&cube_vertex_vec).expect("too many cube vertices");
+    let vertex = Vertex::from_indexed_vertices(&cube_vertices, &cube_indices, gl::STATIC_DRAW);
 
     // init imgui
     let mut imgui_context = imgui::Context::create();
@@ -239,7 +348,31 @@ fn main() {
         z: 0.2,
     };
 
-    let surface_texture_id = image_manager.get_texture_id("surface");
+    let surface_texture_id = image_manager.get_texture_id("surface").unwrap();
+
+    // カメラとライトはuniformブロックにまとめて、毎フレーム1回だけ送る
+    let camera_buffer = UniformBuffer::new(&CameraBlock {
+        view: Matrix4::identity(),
+        projection: Matrix4::identity(),
+        view_position: Vector3::new(camera_x, camera_y, camera_z),
+        _padding: 0.0,
+    });
+    let light_buffer = UniformBuffer::new(&LightBlock {
+        direction: light_direction,
+        _padding0: 0.0,
+        ambient,
+        _padding1: 0.0,
+        diffuse,
+        _padding2: 0.0,
+        specular,
+        _padding3: 0.0,
+    });
+    shader_registry
+        .bind_uniform_block("shader", "Camera", &camera_buffer)
+        .unwrap();
+    shader_registry
+        .bind_uniform_block("shader", "LightBlock", &light_buffer)
+        .unwrap();
 
     let start_time = std::time::Instant::now();
 
@@ -271,6 +404,13 @@ fn main() {
             }
         }
 
+        for id in shader_registry.reload_modified() {
+            match shader_registry.error(&id) {
+                Some(error) => println!("failed to reload shader: id={}, {}", id, error),
+                None => println!("OK: reload shader: id={}", id),
+            }
+        }
+
         unsafe {
             // ! フレームバッファーの紐づけ
             // ! この後の描画処理は用意したフレームバッファーに描画される
@@ -333,24 +473,42 @@ fn main() {
                 100.0,
             );
 
+            camera_buffer.update(&CameraBlock {
+                view: view_matrix,
+                projection: projection_matrix,
+                view_position: Vector3::new(camera_x, camera_y, camera_z),
+                _padding: 0.0,
+            });
+            light_buffer.update(&LightBlock {
+                direction: light_direction,
+                _padding0: 0.0,
+                ambient,
+                _padding1: 0.0,
+                diffuse,
+                _padding2: 0.0,
+                specular,
+                _padding3: 0.0,
+            });
+
             // shader use matrices
+            let shader = shader_registry.get("shader").unwrap();
             shader.use_program();
             shader.set_mat4(c_str!("uModel"), &model_matrix);
-            shader.set_mat4(c_str!("uView"), &view_matrix);
-            shader.set_mat4(c_str!("uProjection"), &projection_matrix);
             shader.set_float(c_str!("uAlpha"), alpha);
-            shader.set_vec3(c_str!("uViewPosition"), camera_x, camera_y, camera_z);
             shader.set_vector3(c_str!("uMaterial.specular"), &material_specular);
             shader.set_float(c_str!("uMaterial.shininess"), material_shininess);
-            shader.set_vector3(c_str!("uLight.direction"), &light_direction);
-            shader.set_vector3(c_str!("uLight.ambient"), &ambient);
-            shader.set_vector3(c_str!("uLight.diffuse"), &diffuse);
-            shader.set_vector3(c_str!("uLight.specular"), &specular);
 
             gl::BindTexture(gl::TEXTURE_2D, surface_texture_id as u32);
             vertex.draw();
             gl::BindTexture(gl::TEXTURE_2D, 0);
 
+            // ! 背景は不透明なものの後に描き、何も描かれていないところだけを塗る
+            // キューブマップは+Yが上なので、Z軸が上のこのシーンに合わせて回転させる
+            if let Ok(sky_texture_id) = image_manager.get_cubemap_id("sky") {
+                let sky_view = view_matrix * Matrix4::from_angle_x(cgmath::Deg(90.0));
+                skybox.draw(sky_texture_id, &sky_view, &projection_matrix);
+            }
+
             // ! フレームバッファーの紐づけ解放
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
 
@@ -385,29 +543,35 @@ fn main() {
 
             frame_buffer.bind_as_texture();
 
+            let screen_shader = match shader_mode {
+                ShaderMode::General => shader_registry.get("screen").unwrap(),
+                ShaderMode::Sphere => shader_registry.get("screen_sphere").unwrap(),
+                ShaderMode::Bloom => shader_registry.get("screen_bloom").unwrap(),
+                ShaderMode::RetroTV => shader_registry.get("screen_retro_tv").unwrap(),
+            };
+            screen_shader.use_program();
+            screen_shader.set_float(c_str!("uGamma"), output_gamma);
+
             match shader_mode {
-                ShaderMode::General => {
-                    screen_shader.use_program();
-                }
-                ShaderMode::Sphere => {
-                    screen_shader_sphere.use_program();
-                }
+                ShaderMode::General | ShaderMode::Sphere => {}
                 ShaderMode::Bloom => {
-                    screen_shader_bloom.use_program();
+                    screen_shader.set_uniform(c_str!("uRatio"), &BLOOM_RATIO);
                 }
                 ShaderMode::RetroTV => {
-                    screen_shader_retro_tv.use_program();
-                    #[rustfmt::skip]
-                    screen_shader_retro_tv
-                        .set_float(c_str!("uScreenHeight"), WINDOW_HEIGHT as f32);
+                    screen_shader.set_float(c_str!("uScreenHeight"), WINDOW_HEIGHT as f32);
                     let now_time = std::time::Instant::now();
-                    screen_shader_retro_tv
-                        .set_float(c_str!("uTime"), (now_time - start_time).as_secs_f32());
+                    screen_shader.set_float(c_str!("uTime"), (now_time - start_time).as_secs_f32());
                 }
             }
 
+            // ! シーンはリニアな色で描いてあるので、画面に書き込むときにsRGBへ変換する
+            if srgb_output {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
             screen_vertex.draw();
             gl::BindTexture(gl::TEXTURE_2D, 0);
+            // imguiの色はもともとsRGBなので、変換しない
+            gl::Disable(gl::FRAMEBUFFER_SRGB);
 
             imgui_sdl2_context.prepare_frame(
                 imgui_context.io_mut(),
@@ -551,41 +715,3 @@ fn main() {
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 }
-
-fn new_screen_vertex_vec(
-    // 生成したい四角形の座標
-    left: f32,
-    top: f32,
-    right: f32,
-    bottom: f32,
-    // 分割数
-    // ! 分割数を1にすると三角形2個で構成された頂点データになる
-    // ! 5を指定すると三角形5o個、10を指定すると200個
-    // * 引数nの時、三角形の数: 2n^2, 頂点の数: 6n^2, floatの数18n^2
-    division: i32,
-) -> std::vec::Vec<f32> {
-    let mut vertex_vec: std::vec::Vec<f32> = std::vec::Vec::new();
-
-    for x in 0..division {
-        for y in 0..division {
-            let l = left + (right - left) / division as f32 * x as f32;
-            let r = left + (right - left) / division as f32 * (x + 1) as f32;
-            let t = top + (bottom - top) / division as f32 * y as f32;
-            let b = top + (bottom - top) / division as f32 * (y + 1) as f32;
-
-            let lc = 1.0 / division as f32 * x as f32;
-            let rc = 1.0 / division as f32 * (x + 1) as f32;
-            let tc = 1.0 / division as f32 * y as f32;
-            let bc = 1.0 / division as f32 * (y + 1) as f32;
-
-            vertex_vec.extend([l, t, 0.0, lc, tc].iter().cloned());
-            vertex_vec.extend([r, t, 0.0, rc, tc].iter().cloned());
-            vertex_vec.extend([l, b, 0.0, lc, bc].iter().cloned());
-            vertex_vec.extend([l, b, 0.0, lc, bc].iter().cloned());
-            vertex_vec.extend([r, t, 0.0, rc, tc].iter().cloned());
-            vertex_vec.extend([r, b, 0.0, rc, bc].iter().cloned());
-        }
-    }
-
-    vertex_vec
-}
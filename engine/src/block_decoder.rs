@@ -0,0 +1,1592 @@
+use crate::compressed_texture::CompressedFormat;
+
+// 圧縮テクスチャーのCPUでの展開
+// ドライバーが対応していない形式のときだけ使う(遅いので、普段はGPUにそのまま渡す)
+
+// 4x4ピクセルのブロックの色(行優先、RGBA)
+type Block = [[u8; 4]; 16];
+
+// BC6Hの展開結果(行優先、RGBA)
+type FloatBlock = [[f32; 4]; 16];
+
+// 展開したピクセル(行優先、RGBA)
+// BC6HはHDRなので、0.0〜1.0に収まらない浮動小数点数のまま返す
+pub(crate) enum DecodedPixels {
+    Rgba8(Vec<u8>),
+    RgbaF32(Vec<f32>),
+}
+
+pub(crate) fn decode(
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> DecodedPixels {
+    let block_size = format.block_size();
+    let decode_block: fn(&[u8]) -> Block = match format {
+        CompressedFormat::Bc1 => |block| decode_bc1(block, true),
+        CompressedFormat::Bc2 => decode_bc2,
+        CompressedFormat::Bc3 => decode_bc3,
+        CompressedFormat::Bc4 => decode_bc4,
+        CompressedFormat::Bc5 => decode_bc5,
+        CompressedFormat::Bc7 => decode_bc7,
+        CompressedFormat::Etc2Rgb => |block| decode_etc2(block, false),
+        CompressedFormat::Etc2RgbA1 => |block| decode_etc2(block, true),
+        CompressedFormat::Etc2Rgba => decode_etc2_eac,
+        CompressedFormat::Bc6hUnsigned => {
+            let values = decode_blocks(block_size, width, height, data, |block| {
+                decode_bc6h(block, false)
+            });
+            return DecodedPixels::RgbaF32(values);
+        }
+        CompressedFormat::Bc6hSigned => {
+            let values = decode_blocks(block_size, width, height, data, |block| {
+                decode_bc6h(block, true)
+            });
+            return DecodedPixels::RgbaF32(values);
+        }
+    };
+    DecodedPixels::Rgba8(decode_blocks(block_size, width, height, data, decode_block))
+}
+
+// 1ブロックずつ展開して、画像の大きさに並べる
+fn decode_blocks<T: Copy + Default>(
+    block_size: usize,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    decode_block: impl Fn(&[u8]) -> [[T; 4]; 16],
+) -> Vec<T> {
+    let blocks_x = width.div_ceil(4) as usize;
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = vec![T::default(); width * height * 4];
+    for (index, block) in data.chunks_exact(block_size).enumerate() {
+        let (block_x, block_y) = (index % blocks_x * 4, index / blocks_x * 4);
+        if block_y >= height {
+            break;
+        }
+        let texels = decode_block(block);
+        // 幅や高さが4の倍数でない画像では、はみ出した部分を捨てる
+        for y in 0..4.min(height - block_y) {
+            for x in 0..4.min(width - block_x) {
+                let offset = ((block_y + y) * width + block_x + x) * 4;
+                pixels[offset..offset + 4].copy_from_slice(&texels[y * 4 + x]);
+            }
+        }
+    }
+    pixels
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 31) as u8;
+    let g = ((color >> 5) & 63) as u8;
+    let b = (color & 31) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+fn mix(a: [u8; 3], b: [u8; 3], weight_a: u32, weight_b: u32) -> [u8; 4] {
+    let total = weight_a + weight_b;
+    let channel = |i: usize| ((a[i] as u32 * weight_a + b[i] as u32 * weight_b) / total) as u8;
+    [channel(0), channel(1), channel(2), 255]
+}
+
+// punch_throughがfalseのとき(BC2/BC3の色)は、常に4色のモードとして読む
+fn decode_bc1(block: &[u8], punch_through: bool) -> Block {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (c0, c1) = (rgb565(color0), rgb565(color1));
+    let palette = if color0 > color1 || !punch_through {
+        [
+            mix(c0, c1, 1, 0),
+            mix(c0, c1, 0, 1),
+            mix(c0, c1, 2, 1),
+            mix(c0, c1, 1, 2),
+        ]
+    } else {
+        [
+            mix(c0, c1, 1, 0),
+            mix(c0, c1, 0, 1),
+            mix(c0, c1, 1, 1),
+            [0, 0, 0, 0],
+        ]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (i * 2)) & 3) as usize];
+    }
+    texels
+}
+
+// BC3のアルファやBC4/BC5のチャンネルで使う、8段階の補間
+fn decode_bc4_channel(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (i * 3)) & 7) as usize];
+    }
+    values
+}
+
+fn decode_bc2(block: &[u8]) -> Block {
+    let mut texels = decode_bc1(&block[8..16], false);
+    let alpha = u64::from_le_bytes([
+        block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let value = ((alpha >> (i * 4)) & 15) as u8;
+        texel[3] = (value << 4) | value;
+    }
+    texels
+}
+
+fn decode_bc3(block: &[u8]) -> Block {
+    let mut texels = decode_bc1(&block[8..16], false);
+    let alpha = decode_bc4_channel(&block[0..8]);
+    for (texel, alpha) in texels.iter_mut().zip(alpha.iter()) {
+        texel[3] = *alpha;
+    }
+    texels
+}
+
+// GL_REDと同じく、(R, 0, 0, 1)として読めるようにする
+fn decode_bc4(block: &[u8]) -> Block {
+    let red = decode_bc4_channel(block);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, red) in texels.iter_mut().zip(red.iter()) {
+        texel[0] = *red;
+    }
+    texels
+}
+
+fn decode_bc5(block: &[u8]) -> Block {
+    let red = decode_bc4_channel(&block[0..8]);
+    let green = decode_bc4_channel(&block[8..16]);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[0] = red[i];
+        texel[1] = green[i];
+    }
+    texels
+}
+
+const ETC_MODIFIERS: [[i32; 4]; 8] = [
+    [2, 8, -2, -8],
+    [5, 17, -5, -17],
+    [9, 29, -9, -29],
+    [13, 42, -13, -42],
+    [18, 60, -18, -60],
+    [24, 80, -24, -80],
+    [33, 106, -33, -106],
+    [47, 183, -47, -183],
+];
+
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+fn extend_4(value: u8) -> i32 {
+    ((value << 4) | value) as i32
+}
+
+fn extend_5(value: u8) -> i32 {
+    ((value << 3) | (value >> 2)) as i32
+}
+
+fn clamp_color(color: [i32; 3]) -> [u8; 4] {
+    [
+        color[0].clamp(0, 255) as u8,
+        color[1].clamp(0, 255) as u8,
+        color[2].clamp(0, 255) as u8,
+        255,
+    ]
+}
+
+fn offset_color(color: [i32; 3], offset: i32) -> [u8; 4] {
+    clamp_color([color[0] + offset, color[1] + offset, color[2] + offset])
+}
+
+// 3ビットの符号付き整数
+fn delta(value: u8) -> i32 {
+    ((value as i32 & 7) << 29) >> 29
+}
+
+// punch_throughはRGB8A1で、差分のビットが不透明かどうかのビットになる
+fn decode_etc2(block: &[u8], punch_through: bool) -> Block {
+    let bits = u64::from_be_bytes([
+        block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+    // ピクセルのインデックスは列優先(x * 4 + y)で並んでいる
+    let index = |x: usize, y: usize| {
+        let i = x * 4 + y;
+        let msb = (bits >> (16 + i)) & 1;
+        let lsb = (bits >> i) & 1;
+        ((msb << 1) | lsb) as usize
+    };
+    let differential = (block[3] & 2) != 0;
+    let opaque = !punch_through || differential;
+    let mut texels = [[0; 4]; 16];
+
+    let (r, g, b) = (block[0] >> 3, block[1] >> 3, block[2] >> 3);
+    let (r2, g2, b2) = (
+        r as i32 + delta(block[0]),
+        g as i32 + delta(block[1]),
+        b as i32 + delta(block[2]),
+    );
+    let individual = !punch_through && !differential;
+
+    if !individual && !(0..32).contains(&r2) {
+        // Tモード
+        let c1 = [
+            extend_4(((block[0] >> 1) & 12) | (block[0] & 3)),
+            extend_4(block[1] >> 4),
+            extend_4(block[1] & 15),
+        ];
+        let c2 = [
+            extend_4(block[2] >> 4),
+            extend_4(block[2] & 15),
+            extend_4(block[3] >> 4),
+        ];
+        let distance = ETC_DISTANCES[(((block[3] >> 1) & 6) | (block[3] & 1)) as usize];
+        let paints = [
+            clamp_color(c1),
+            offset_color(c2, distance),
+            clamp_color(c2),
+            offset_color(c2, -distance),
+        ];
+        for x in 0..4 {
+            for y in 0..4 {
+                texels[y * 4 + x] = paint(&paints, index(x, y), opaque);
+            }
+        }
+    } else if !individual && !(0..32).contains(&g2) {
+        // Hモード
+        let r1 = (block[0] >> 3) & 15;
+        let g1 = ((block[0] & 7) << 1) | ((block[1] >> 4) & 1);
+        let b1 = (block[1] & 8) | ((block[1] & 3) << 1) | (block[2] >> 7);
+        let r2 = (block[2] >> 3) & 15;
+        let g2 = ((block[2] & 7) << 1) | (block[3] >> 7);
+        let b2 = (block[3] >> 3) & 15;
+        let value1 = ((r1 as u32) << 8) | ((g1 as u32) << 4) | b1 as u32;
+        let value2 = ((r2 as u32) << 8) | ((g2 as u32) << 4) | b2 as u32;
+        let distance_index = (block[3] & 4) | ((block[3] & 1) << 1) | (value1 >= value2) as u8;
+        let distance = ETC_DISTANCES[distance_index as usize];
+        let c1 = [extend_4(r1), extend_4(g1), extend_4(b1)];
+        let c2 = [extend_4(r2), extend_4(g2), extend_4(b2)];
+        let paints = [
+            offset_color(c1, distance),
+            offset_color(c1, -distance),
+            offset_color(c2, distance),
+            offset_color(c2, -distance),
+        ];
+        for x in 0..4 {
+            for y in 0..4 {
+                texels[y * 4 + x] = paint(&paints, index(x, y), opaque);
+            }
+        }
+    } else if !individual && !(0..32).contains(&b2) {
+        // 平面モード(透明にはならない)
+        let field = |shift: u32, mask: u64| ((bits >> shift) & mask) as i32;
+        let extend_6 = |value: i32| (value << 2) | (value >> 4);
+        let extend_7 = |value: i32| (value << 1) | (value >> 6);
+        let origin = [
+            extend_6(field(57, 63)),
+            extend_7((field(56, 1) << 6) | field(49, 63)),
+            extend_6((field(48, 1) << 5) | (field(43, 3) << 3) | field(39, 7)),
+        ];
+        let horizontal = [
+            extend_6((field(34, 31) << 1) | field(32, 1)),
+            extend_7(field(25, 127)),
+            extend_6(field(19, 63)),
+        ];
+        let vertical = [
+            extend_6(field(13, 63)),
+            extend_7(field(6, 127)),
+            extend_6(field(0, 63)),
+        ];
+        for x in 0..4 {
+            for y in 0..4 {
+                let channel = |c: usize| {
+                    (x as i32 * (horizontal[c] - origin[c])
+                        + y as i32 * (vertical[c] - origin[c])
+                        + 4 * origin[c]
+                        + 2)
+                        >> 2
+                };
+                texels[y * 4 + x] = clamp_color([channel(0), channel(1), channel(2)]);
+            }
+        }
+    } else {
+        // 個別モードと差分モード(ETC1と同じ)
+        let (base1, base2) = if individual {
+            (
+                [
+                    extend_4(block[0] >> 4),
+                    extend_4(block[1] >> 4),
+                    extend_4(block[2] >> 4),
+                ],
+                [
+                    extend_4(block[0] & 15),
+                    extend_4(block[1] & 15),
+                    extend_4(block[2] & 15),
+                ],
+            )
+        } else {
+            (
+                [extend_5(r), extend_5(g), extend_5(b)],
+                [extend_5(r2 as u8), extend_5(g2 as u8), extend_5(b2 as u8)],
+            )
+        };
+        let tables = [
+            ETC_MODIFIERS[(block[3] >> 5) as usize],
+            ETC_MODIFIERS[((block[3] >> 2) & 7) as usize],
+        ];
+        let flip = (block[3] & 1) != 0;
+        for x in 0..4 {
+            for y in 0..4 {
+                let second = if flip { y >= 2 } else { x >= 2 };
+                let (base, table) = if second {
+                    (base2, tables[1])
+                } else {
+                    (base1, tables[0])
+                };
+                let pixel_index = index(x, y);
+                texels[y * 4 + x] = if !opaque && pixel_index == 2 {
+                    [0, 0, 0, 0]
+                } else if !opaque && pixel_index == 0 {
+                    // 透明になりうるブロックでは、小さい方の補正値が0になる
+                    clamp_color(base)
+                } else {
+                    offset_color(base, table[pixel_index])
+                };
+            }
+        }
+    }
+    texels
+}
+
+// T/Hモードでは、不透明でないブロックのインデックス2が透明になる
+fn paint(paints: &[[u8; 4]; 4], index: usize, opaque: bool) -> [u8; 4] {
+    if !opaque && index == 2 {
+        [0, 0, 0, 0]
+    } else {
+        paints[index]
+    }
+}
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// 先頭8バイトがEACのアルファ、後ろ8バイトがETC2の色
+fn decode_etc2_eac(block: &[u8]) -> Block {
+    let mut texels = decode_etc2(&block[8..16], false);
+    let base = block[0] as i32;
+    let multiplier = (block[1] >> 4) as i32;
+    let table = EAC_MODIFIERS[(block[1] & 15) as usize];
+    let bits = u64::from_be_bytes([
+        0, 0, block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+    for x in 0..4 {
+        for y in 0..4 {
+            let i = x * 4 + y;
+            let index = ((bits >> (45 - i * 3)) & 7) as usize;
+            texels[y * 4 + x][3] = (base + table[index] * multiplier).clamp(0, 255) as u8;
+        }
+    }
+    texels
+}
+
+// BPTC(BC6H/BC7)のビット列を下位ビットから順に読む
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> BitReader {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&block[..16]);
+        BitReader {
+            bits: u128::from_le_bytes(bytes),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits >> self.position) as u32 & ((1u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+// 2つの領域に分けるときの分け方(ビットが立っているピクセルが2つ目の領域)
+// BC6Hは先頭の32個だけを使う
+const BPTC_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+// 3つの領域に分けるときの、各ピクセルの領域
+const BPTC_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// 各領域の最初のピクセル(アンカー)は、インデックスの最上位ビットが省かれている
+// 1つ目の領域のアンカーは常にピクセル0
+const BPTC_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BPTC_ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+const BPTC_ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+// インデックスのビット数ごとの、2つの端点の補間の重み(64分率)
+const BPTC_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BPTC_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BPTC_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn bptc_weight(index_bits: u32, index: u32) -> u32 {
+    match index_bits {
+        2 => BPTC_WEIGHTS_2[index as usize],
+        3 => BPTC_WEIGHTS_3[index as usize],
+        _ => BPTC_WEIGHTS_4[index as usize],
+    }
+}
+
+fn bptc_subset(subsets: usize, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => ((BPTC_PARTITIONS_2[partition] >> pixel) & 1) as usize,
+        _ => BPTC_PARTITIONS_3[partition][pixel] as usize,
+    }
+}
+
+fn is_anchor(subsets: usize, partition: usize, pixel: usize) -> bool {
+    let anchor = match (subsets, bptc_subset(subsets, partition, pixel)) {
+        (_, 0) => 0,
+        (2, _) => BPTC_ANCHORS_2[partition],
+        (_, 1) => BPTC_ANCHORS_3_SECOND[partition],
+        _ => BPTC_ANCHORS_3_THIRD[partition],
+    };
+    anchor as usize == pixel
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    // 端点ごとのPビットと、領域ごとに共有するPビット
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    // 0でなければ、色とアルファで別々のインデックスを持つ
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+// 足りない下位ビットを上位ビットの繰り返しで埋めて8ビットに広げる
+fn extend_bits(value: u32, bits: u32) -> u32 {
+    (value << (8 - bits)) | (value >> (2 * bits - 8))
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+// 先頭バイトの最下位から数えて、最初に立っているビットの位置がモード
+fn decode_bc7(block: &[u8]) -> Block {
+    let mut reader = BitReader::new(block);
+    let mode = match (0..8).find(|_| reader.read(1) == 1) {
+        Some(mode) => &BC7_MODES[mode],
+        // モードのビットが1つも立っていないブロックは不正なので、透明な黒にする
+        None => return [[0; 4]; 16],
+    };
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // 端点はR、G、B、Aの順に、全ての端点の分が並んでいる
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = reader.read(mode.alpha_bits);
+    }
+
+    let (color_bits, alpha_bits) = if mode.endpoint_pbits || mode.shared_pbits {
+        for i in 0..endpoint_count {
+            let pbit = if mode.endpoint_pbits || i % 2 == 0 {
+                reader.read(1)
+            } else {
+                endpoints[i - 1][0] & 1
+            };
+            for value in endpoints[i].iter_mut() {
+                *value = (*value << 1) | pbit;
+            }
+        }
+        (mode.color_bits + 1, mode.alpha_bits + 1)
+    } else {
+        (mode.color_bits, mode.alpha_bits)
+    };
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint[..3].iter_mut() {
+            *value = extend_bits(*value, color_bits);
+        }
+        endpoint[3] = if mode.alpha_bits == 0 {
+            255
+        } else {
+            extend_bits(endpoint[3], alpha_bits)
+        };
+    }
+
+    let mut indices = [0; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, pixel) as u32;
+        *index = reader.read(mode.index_bits - anchor);
+    }
+    let mut secondary_indices = indices;
+    if mode.secondary_index_bits != 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            let anchor = (pixel == 0) as u32;
+            *index = reader.read(mode.secondary_index_bits - anchor);
+        }
+    }
+    // モード4のインデックス選択ビットが立っていれば、色とアルファのインデックスを入れ替える
+    let (color_indices, color_index_bits, alpha_indices, alpha_index_bits) = if index_selection == 0
+    {
+        (
+            indices,
+            mode.index_bits,
+            secondary_indices,
+            mode.secondary_index_bits.max(mode.index_bits),
+        )
+    } else {
+        (
+            secondary_indices,
+            mode.secondary_index_bits,
+            indices,
+            mode.index_bits,
+        )
+    };
+
+    let mut texels = [[0; 4]; 16];
+    for (pixel, texel) in texels.iter_mut().enumerate() {
+        let subset = bptc_subset(mode.subsets, partition, pixel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        let color_weight = bptc_weight(color_index_bits, color_indices[pixel]);
+        let alpha_weight = bptc_weight(alpha_index_bits, alpha_indices[pixel]);
+        for channel in 0..3 {
+            texel[channel] = interpolate(e0[channel], e1[channel], color_weight) as u8;
+        }
+        texel[3] = interpolate(e0[3], e1[3], alpha_weight) as u8;
+        // 回転のビットで、アルファと入れ替えられている色のチャンネルを戻す
+        if rotation != 0 {
+            texel.swap(rotation as usize - 1, 3);
+        }
+    }
+    texels
+}
+
+// BC6Hの端点のビットの並び(読み込み先、その位置、ビット数)
+// 読み込み先は、端点w、x、y、zのR、G、Bの順に0〜11の番号で表す
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+
+type Bc6hLayout = &'static [(usize, u32, u32)];
+
+struct Bc6hMode {
+    // ブロックの先頭に書かれているモードの値(2ビットか5ビット)
+    mode: u32,
+    // 2つ目以降の端点が、1つ目の端点との差分になっているかどうか
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    layout: Bc6hLayout,
+}
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        mode: 0,
+        transformed: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (GY, 4, 1),
+            (BY, 4, 1),
+            (BZ, 4, 1),
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 1,
+        transformed: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (GY, 5, 1),
+            (GZ, 4, 1),
+            (GZ, 5, 1),
+            (RW, 0, 7),
+            (BZ, 0, 1),
+            (BZ, 1, 1),
+            (BY, 4, 1),
+            (GW, 0, 7),
+            (BY, 5, 1),
+            (BZ, 2, 1),
+            (GY, 4, 1),
+            (BW, 0, 7),
+            (BZ, 3, 1),
+            (BZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 6),
+            (GY, 0, 4),
+            (GX, 0, 6),
+            (GZ, 0, 4),
+            (BX, 0, 6),
+            (BY, 0, 4),
+            (RY, 0, 6),
+            (RZ, 0, 6),
+        ],
+    },
+    Bc6hMode {
+        mode: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 5),
+            (RW, 10, 1),
+            (GY, 0, 4),
+            (GX, 0, 4),
+            (GW, 10, 1),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 4),
+            (BW, 10, 1),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 6,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 4),
+            (RW, 10, 1),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (GW, 10, 1),
+            (GZ, 0, 4),
+            (BX, 0, 4),
+            (BW, 10, 1),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 4),
+            (BZ, 0, 1),
+            (BZ, 2, 1),
+            (RZ, 0, 4),
+            (GY, 4, 1),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 10,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 4),
+            (RW, 10, 1),
+            (BY, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 4),
+            (GW, 10, 1),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BW, 10, 1),
+            (BY, 0, 4),
+            (RY, 0, 4),
+            (BZ, 1, 1),
+            (BZ, 2, 1),
+            (RZ, 0, 4),
+            (BZ, 4, 1),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 14,
+        transformed: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (RW, 0, 9),
+            (BY, 4, 1),
+            (GW, 0, 9),
+            (GY, 4, 1),
+            (BW, 0, 9),
+            (BZ, 4, 1),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 18,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        layout: &[
+            (RW, 0, 8),
+            (GZ, 4, 1),
+            (BY, 4, 1),
+            (GW, 0, 8),
+            (BZ, 2, 1),
+            (GY, 4, 1),
+            (BW, 0, 8),
+            (BZ, 3, 1),
+            (BZ, 4, 1),
+            (RX, 0, 6),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 6),
+            (RZ, 0, 6),
+        ],
+    },
+    Bc6hMode {
+        mode: 22,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        layout: &[
+            (RW, 0, 8),
+            (BZ, 0, 1),
+            (BY, 4, 1),
+            (GW, 0, 8),
+            (GY, 5, 1),
+            (GY, 4, 1),
+            (BW, 0, 8),
+            (GZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 6),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 26,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        layout: &[
+            (RW, 0, 8),
+            (BZ, 1, 1),
+            (BY, 4, 1),
+            (GW, 0, 8),
+            (BY, 5, 1),
+            (GY, 4, 1),
+            (BW, 0, 8),
+            (BZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 6),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 30,
+        transformed: false,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (RW, 0, 6),
+            (GZ, 4, 1),
+            (BZ, 0, 1),
+            (BZ, 1, 1),
+            (BY, 4, 1),
+            (GW, 0, 6),
+            (GY, 5, 1),
+            (BY, 5, 1),
+            (BZ, 2, 1),
+            (GY, 4, 1),
+            (BW, 0, 6),
+            (GZ, 5, 1),
+            (BZ, 3, 1),
+            (BZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 6),
+            (GY, 0, 4),
+            (GX, 0, 6),
+            (GZ, 0, 4),
+            (BX, 0, 6),
+            (BY, 0, 4),
+            (RY, 0, 6),
+            (RZ, 0, 6),
+        ],
+    },
+    Bc6hMode {
+        mode: 3,
+        transformed: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 10),
+            (GX, 0, 10),
+            (BX, 0, 10),
+        ],
+    },
+    Bc6hMode {
+        mode: 7,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 9),
+            (RW, 10, 1),
+            (GX, 0, 9),
+            (GW, 10, 1),
+            (BX, 0, 9),
+            (BW, 10, 1),
+        ],
+    },
+    // 上位ビットは逆順に並んでいる
+    Bc6hMode {
+        mode: 11,
+        transformed: true,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 8),
+            (RW, 11, 1),
+            (RW, 10, 1),
+            (GX, 0, 8),
+            (GW, 11, 1),
+            (GW, 10, 1),
+            (BX, 0, 8),
+            (BW, 11, 1),
+            (BW, 10, 1),
+        ],
+    },
+    Bc6hMode {
+        mode: 15,
+        transformed: true,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        layout: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 4),
+            (RW, 15, 1),
+            (RW, 14, 1),
+            (RW, 13, 1),
+            (RW, 12, 1),
+            (RW, 11, 1),
+            (RW, 10, 1),
+            (GX, 0, 4),
+            (GW, 15, 1),
+            (GW, 14, 1),
+            (GW, 13, 1),
+            (GW, 12, 1),
+            (GW, 11, 1),
+            (GW, 10, 1),
+            (BX, 0, 4),
+            (BW, 15, 1),
+            (BW, 14, 1),
+            (BW, 13, 1),
+            (BW, 12, 1),
+            (BW, 11, 1),
+            (BW, 10, 1),
+        ],
+    },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    (value << (32 - bits)) >> (32 - bits)
+}
+
+// 端点を16ビットの範囲へ広げる
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xFFFF
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 {
+        value
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+// 補間した値を、半精度浮動小数点数のビットとして読めるように縮める
+fn finish_unquantize(value: i32, signed: bool) -> f32 {
+    let bits = if !signed {
+        (value * 31) >> 6
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5)
+    } else {
+        (value * 31) >> 5
+    };
+    half::f16::from_bits(bits as u16).to_f32()
+}
+
+fn decode_bc6h(block: &[u8], signed: bool) -> FloatBlock {
+    let mut reader = BitReader::new(block);
+    let mut mode = reader.read(2);
+    if mode >= 2 {
+        mode |= reader.read(3) << 2;
+    }
+    let mode = match BC6H_MODES.iter().find(|candidate| candidate.mode == mode) {
+        Some(mode) => mode,
+        // 予約されているモードのブロックは黒にする
+        None => return [[0.0, 0.0, 0.0, 1.0]; 16],
+    };
+
+    let mut fields = [0; 12];
+    for &(field, shift, count) in mode.layout {
+        fields[field] |= (reader.read(count) as i32) << shift;
+    }
+    // モードの下位2ビットが3なら1つの領域、それ以外は2つの領域
+    let subsets = if mode.mode & 3 == 3 { 1 } else { 2 };
+    let partition = if subsets == 2 {
+        reader.read(5) as usize
+    } else {
+        0
+    };
+
+    let bits = mode.endpoint_bits;
+    let mut endpoints = [[0; 3]; 4];
+    for endpoint in 0..subsets * 2 {
+        for channel in 0..3 {
+            let value = fields[endpoint * 3 + channel];
+            let value = if endpoint == 0 || !mode.transformed {
+                value
+            } else {
+                let delta = sign_extend(value, mode.delta_bits[channel]);
+                (endpoints[0][channel] + delta) & ((1 << bits) - 1)
+            };
+            endpoints[endpoint][channel] = if signed {
+                sign_extend(value, bits)
+            } else {
+                value
+            };
+        }
+    }
+    for endpoint in endpoints.iter_mut() {
+        for value in endpoint.iter_mut() {
+            *value = unquantize(*value, bits, signed);
+        }
+    }
+
+    let index_bits = if subsets == 2 { 3 } else { 4 };
+    let mut texels = [[0.0; 4]; 16];
+    for (pixel, texel) in texels.iter_mut().enumerate() {
+        let anchor = is_anchor(subsets, partition, pixel) as u32;
+        let weight = bptc_weight(index_bits, reader.read(index_bits - anchor)) as i32;
+        let subset = bptc_subset(subsets, partition, pixel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        for channel in 0..3 {
+            let value = ((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6;
+            texel[channel] = finish_unquantize(value, signed);
+        }
+        texel[3] = 1.0;
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bc1_four_color_block() {
+        // 赤(0xF800)と青(0x001F)、1行目のインデックスが0, 1, 2, 3
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0x00, 0x00, 0x00];
+        let texels = decode_bc1(&block, true);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+        assert_eq!(texels[4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc1_punch_through_block() {
+        // color0 <= color1なので、インデックス3は透明な黒
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0xC4, 0x00, 0x00, 0x00];
+        let texels = decode_bc1(&block, true);
+        assert_eq!(texels[0], [0, 0, 255, 255]);
+        assert_eq!(texels[1], [255, 0, 0, 255]);
+        assert_eq!(texels[3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc3_alpha_block() {
+        // アルファ224と0の8段階、インデックスが0, 1, 2, 7、色は白
+        let block = [224, 0, 0x88, 0x0E, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0];
+        let texels = decode_bc3(&block);
+        assert_eq!(texels[0], [255, 255, 255, 224]);
+        assert_eq!(texels[1], [255, 255, 255, 0]);
+        assert_eq!(texels[2], [255, 255, 255, 192]);
+        assert_eq!(texels[3], [255, 255, 255, 32]);
+        assert_eq!(texels[15], [255, 255, 255, 224]);
+    }
+
+    #[test]
+    fn bc4_six_value_block() {
+        // a0 <= a1なので6段階と0、255
+        let block = [0, 250, 0x02, 0x0E, 0, 0, 0, 0];
+        let red: Vec<u8> = decode_bc4(&block).iter().map(|texel| texel[0]).collect();
+        assert_eq!(&red[..4], &[50, 0, 0, 255]);
+    }
+
+    #[test]
+    fn etc2_individual_block() {
+        // 基本色0x88、表0(+2, +8, -2, -8)
+        // (0, 0)のインデックスは1、(0, 1)のインデックスは2
+        let block = [0x88, 0x88, 0x88, 0x00, 0x00, 0x02, 0x00, 0x01];
+        let texels = decode_etc2(&block, false);
+        assert_eq!(texels[0], [144, 144, 144, 255]);
+        assert_eq!(texels[4], [134, 134, 134, 255]);
+        assert_eq!(texels[1], [138, 138, 138, 255]);
+    }
+
+    #[test]
+    fn etc2_differential_block() {
+        // 左半分は16(132)、右半分は16 - 1(123)
+        let block = [0x87, 0x87, 0x87, 0x02, 0x00, 0x00, 0x00, 0x00];
+        let texels = decode_etc2(&block, false);
+        assert_eq!(texels[0], [134, 134, 134, 255]);
+        assert_eq!(texels[1], [134, 134, 134, 255]);
+        assert_eq!(texels[2], [125, 125, 125, 255]);
+        assert_eq!(texels[15], [125, 125, 125, 255]);
+    }
+
+    #[test]
+    fn etc2_punch_through_transparent_texel() {
+        // 差分のビットが0なので透明になりうるブロックで、(0, 0)のインデックスが2
+        let block = [0x87, 0x87, 0x87, 0x00, 0x00, 0x01, 0x00, 0x00];
+        let texels = decode_etc2(&block, true);
+        assert_eq!(texels[0], [0, 0, 0, 0]);
+        assert_eq!(texels[1], [132, 132, 132, 255]);
+    }
+
+    #[test]
+    fn eac_alpha_block() {
+        // 基本値128、倍率1、表0(-3, -6, -9, -15, 2, 5, 8, 14)
+        // (0, 0)のインデックスは4、(0, 1)のインデックスは7
+        let mut block = [0; 16];
+        block[..3].copy_from_slice(&[128, 0x10, 0x9C]);
+        block[8..12].copy_from_slice(&[0x88, 0x88, 0x88, 0x00]);
+        let texels = decode_etc2_eac(&block);
+        assert_eq!(texels[0], [138, 138, 138, 130]);
+        assert_eq!(texels[4], [138, 138, 138, 142]);
+        assert_eq!(texels[1], [138, 138, 138, 125]);
+    }
+
+    #[test]
+    fn decode_crops_partial_blocks() {
+        // 6x2の画像は2ブロックで、それぞれ右と下がはみ出す
+        let red = [0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0];
+        let blue = [0x1F, 0x00, 0x1F, 0x00, 0, 0, 0, 0];
+        let data = [red, blue].concat();
+        let pixels = match decode(CompressedFormat::Bc1, 6, 2, &data) {
+            DecodedPixels::Rgba8(pixels) => pixels,
+            DecodedPixels::RgbaF32(_) => panic!("BC1 should decode to RGBA8"),
+        };
+        assert_eq!(pixels.len(), 6 * 2 * 4);
+        assert_eq!(&pixels[12..16], &[255, 0, 0, 255]);
+        assert_eq!(&pixels[16..20], &[0, 0, 255, 255]);
+        assert_eq!(&pixels[44..48], &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn bptc_anchors_lie_in_their_subsets() {
+        for partition in 0..64 {
+            for subsets in 2..=3 {
+                assert_eq!(bptc_subset(subsets, partition, 0), 0);
+                for subset in 0..subsets {
+                    let anchors: Vec<usize> = (0..16)
+                        .filter(|&pixel| bptc_subset(subsets, partition, pixel) == subset)
+                        .filter(|&pixel| is_anchor(subsets, partition, pixel))
+                        .collect();
+                    assert_eq!(anchors.len(), 1, "partition={}", partition);
+                }
+            }
+        }
+    }
+
+    // BC7とBC6Hの参照値は、Mesa(llvmpipe)でglGetTexImageして読み戻したもの
+    // ピクセル0、5、15を比べる
+    #[test]
+    fn bc7_blocks_match_reference() {
+        let cases: [([u8; 16], [[u8; 4]; 3]); 5] = [
+            // モード0(3つの領域、端点ごとのPビット)
+            (
+                [
+                    35, 145, 216, 205, 195, 16, 65, 30, 126, 194, 115, 120, 166, 97, 201, 53,
+                ],
+                [[146, 173, 95, 255], [141, 53, 224, 255], [93, 112, 43, 255]],
+            ),
+            // モード1(2つの領域、共有するPビット)
+            (
+                [
+                    26, 124, 7, 228, 213, 99, 110, 155, 195, 196, 0, 178, 114, 68, 184, 205,
+                ],
+                [[241, 84, 108, 255], [206, 77, 93, 255], [97, 134, 111, 255]],
+            ),
+            // モード4(回転とインデックス選択あり)
+            (
+                [
+                    48, 194, 117, 243, 74, 237, 5, 106, 214, 234, 142, 236, 164, 25, 47, 161,
+                ],
+                [[175, 177, 96, 48], [202, 177, 96, 48], [147, 177, 96, 48]],
+            ),
+            // モード5(回転あり)
+            (
+                [
+                    224, 185, 220, 75, 30, 190, 85, 229, 184, 249, 182, 128, 239, 247, 108, 129,
+                ],
+                [[114, 94, 76, 195], [114, 229, 76, 110], [114, 229, 66, 110]],
+            ),
+            // モード6(1つの領域、4ビットのインデックス)
+            (
+                [
+                    192, 233, 171, 48, 77, 72, 150, 249, 225, 127, 216, 240, 129, 100, 150, 218,
+                ],
+                [[167, 11, 19, 151], [105, 145, 34, 230], [105, 145, 34, 230]],
+            ),
+        ];
+        for (block, expected) in cases.iter() {
+            let texels = decode_bc7(block);
+            assert_eq!([texels[0], texels[5], texels[15]], *expected);
+        }
+    }
+
+    #[test]
+    fn bc7_without_mode_bit_is_transparent_black() {
+        let block = [
+            0, 22, 112, 169, 130, 27, 199, 41, 133, 215, 100, 94, 125, 187, 7, 120,
+        ];
+        assert_eq!(decode_bc7(&block), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn bc6h_blocks_match_reference() {
+        let cases: [([u8; 16], bool, [[u16; 4]; 3]); 6] = [
+            // モード0(2つの領域、差分の端点)
+            (
+                [
+                    96, 242, 95, 201, 165, 60, 126, 143, 151, 214, 201, 51, 60, 148, 5, 253,
+                ],
+                false,
+                [
+                    [0x6E73, 0x54AD, 0x599A, 0x3C00],
+                    [0x7055, 0x56F4, 0x5AE9, 0x3C00],
+                    [0x704B, 0x56EF, 0x5AF8, 0x3C00],
+                ],
+            ),
+            (
+                [
+                    156, 163, 235, 13, 217, 205, 165, 116, 246, 74, 11, 85, 83, 192, 70, 11,
+                ],
+                true,
+                [
+                    [0x448F, 0x8918, 0x212F, 0x3C00],
+                    [0x4271, 0x8CE3, 0x1E42, 0x3C00],
+                    [0x44E7, 0x8A0D, 0x2093, 0x3C00],
+                ],
+            ),
+            // モード3(1つの領域、そのままの端点)
+            (
+                [
+                    3, 168, 54, 184, 176, 250, 104, 247, 98, 47, 240, 231, 169, 39, 79, 10,
+                ],
+                false,
+                [
+                    [0x2ADA, 0x12C8, 0x0E3E, 0x3C00],
+                    [0x6779, 0x65A8, 0x3BE1, 0x3C00],
+                    [0x26CF, 0x0D42, 0x0B33, 0x3C00],
+                ],
+            ),
+            (
+                [
+                    99, 155, 152, 102, 84, 0, 103, 162, 12, 107, 211, 167, 204, 128, 42, 31,
+                ],
+                true,
+                [
+                    [0x2098, 0x1834, 0xA26F, 0x3C00],
+                    [0x09A9, 0x9F53, 0x33D2, 0x3C00],
+                    [0x31FF, 0x4254, 0xE3DF, 0x3C00],
+                ],
+            ),
+            // モード15(上位ビットが逆順の16ビットの端点)
+            (
+                [
+                    207, 118, 90, 233, 97, 85, 40, 226, 229, 100, 86, 20, 122, 171, 120, 98,
+                ],
+                false,
+                [
+                    [0x2A7B, 0x14AF, 0x1D86, 0x3C00],
+                    [0x2A7B, 0x14AF, 0x1D86, 0x3C00],
+                    [0x2A7B, 0x14AF, 0x1D87, 0x3C00],
+                ],
+            ),
+            (
+                [
+                    239, 77, 121, 170, 251, 202, 171, 146, 57, 211, 245, 172, 80, 218, 180, 170,
+                ],
+                true,
+                [
+                    [0xD2E4, 0xD456, 0x4B67, 0x3C00],
+                    [0xD2E5, 0xD457, 0x4B6B, 0x3C00],
+                    [0xD2E5, 0xD456, 0x4B69, 0x3C00],
+                ],
+            ),
+        ];
+        for (block, signed, expected) in cases.iter() {
+            let texels = decode_bc6h(block, *signed);
+            // 半精度浮動小数点数のビットで比べる
+            let bits = |texel: [f32; 4]| texel.map(|value| half::f16::from_f32(value).to_bits());
+            assert_eq!(
+                [bits(texels[0]), bits(texels[5]), bits(texels[15])],
+                *expected
+            );
+        }
+    }
+
+    #[test]
+    fn bc6h_reserved_mode_is_black() {
+        let block = [
+            19, 120, 129, 172, 91, 188, 35, 72, 119, 31, 70, 190, 54, 176, 36, 16,
+        ];
+        assert_eq!(decode_bc6h(&block, false), [[0.0, 0.0, 0.0, 1.0]; 16]);
+    }
+
+    #[test]
+    fn bc6h_decodes_to_float_pixels() {
+        match decode(CompressedFormat::Bc6hUnsigned, 2, 2, &[0; 16]) {
+            DecodedPixels::RgbaF32(values) => assert_eq!(values.len(), 2 * 2 * 4),
+            DecodedPixels::Rgba8(_) => panic!("BC6H should decode to floats"),
+        }
+    }
+}
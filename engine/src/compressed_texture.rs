@@ -0,0 +1,455 @@
+use std::convert::{TryFrom, TryInto};
+
+use gl::types::GLenum;
+
+use crate::image_manager::DecodeError;
+
+// S3TC(BC1〜BC3)はGL_EXT_texture_compression_s3tcの定数で、glクレートの定義に含まれていない
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+
+// 4x4ピクセルのブロック単位で圧縮された形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompressedFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6hUnsigned,
+    Bc6hSigned,
+    Bc7,
+    Etc2Rgb,
+    Etc2RgbA1,
+    Etc2Rgba,
+}
+
+impl CompressedFormat {
+    // 1ブロックのバイト数
+    pub fn block_size(self) -> usize {
+        match self {
+            CompressedFormat::Bc1
+            | CompressedFormat::Bc4
+            | CompressedFormat::Etc2Rgb
+            | CompressedFormat::Etc2RgbA1 => 8,
+            _ => 16,
+        }
+    }
+
+    // sRGBの形式がないもの(BC4/BC5/BC6H)は、srgbを無視する
+    pub fn gl_enum(self, srgb: bool) -> GLenum {
+        match (self, srgb) {
+            (CompressedFormat::Bc1, false) => COMPRESSED_RGBA_S3TC_DXT1,
+            (CompressedFormat::Bc1, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            (CompressedFormat::Bc2, false) => COMPRESSED_RGBA_S3TC_DXT3,
+            (CompressedFormat::Bc2, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            (CompressedFormat::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5,
+            (CompressedFormat::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            (CompressedFormat::Bc4, _) => gl::COMPRESSED_RED_RGTC1,
+            (CompressedFormat::Bc5, _) => gl::COMPRESSED_RG_RGTC2,
+            (CompressedFormat::Bc6hUnsigned, _) => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            (CompressedFormat::Bc6hSigned, _) => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            (CompressedFormat::Bc7, false) => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            (CompressedFormat::Bc7, true) => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            (CompressedFormat::Etc2Rgb, false) => gl::COMPRESSED_RGB8_ETC2,
+            (CompressedFormat::Etc2Rgb, true) => gl::COMPRESSED_SRGB8_ETC2,
+            (CompressedFormat::Etc2RgbA1, false) => gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (CompressedFormat::Etc2RgbA1, true) => gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (CompressedFormat::Etc2Rgba, false) => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            (CompressedFormat::Etc2Rgba, true) => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        }
+    }
+
+    // level番目のミップマップのバイト数
+    // ヘッダーの大きさがでたらめでも、あふれずにエラーを返す
+    fn level_size(self, width: u32, height: u32, level: usize) -> Result<usize, DecodeError> {
+        let (width, height) = level_dimensions(width, height, level);
+        (width.div_ceil(4) as usize)
+            .checked_mul(height.div_ceil(4) as usize)
+            .and_then(|blocks| blocks.checked_mul(self.block_size()))
+            .ok_or_else(|| DecodeError::Format(format!("mip level {} is too large", level)))
+    }
+}
+
+// level番目のミップマップの縦横のピクセル数
+pub(crate) fn level_dimensions(width: u32, height: u32, level: usize) -> (u32, u32) {
+    let shift = |size: u32| size.checked_shr(level as u32).unwrap_or(0).max(1);
+    (shift(width), shift(height))
+}
+
+// ファイルに書かれたミップマップの数が、大きさから作れる数(1x1まで)を超えていないか確かめる
+fn check_level_count(width: u32, height: u32, level_count: u32) -> Result<usize, DecodeError> {
+    if width == 0 || height == 0 {
+        return Err(DecodeError::Format(format!(
+            "invalid size {}x{}",
+            width, height
+        )));
+    }
+    let max_level_count = 32 - width.max(height).leading_zeros();
+    if level_count > max_level_count {
+        return Err(DecodeError::Format(format!(
+            "{} mip levels for {}x{}, expected at most {}",
+            level_count, width, height, max_level_count
+        )));
+    }
+    Ok(level_count.max(1) as usize)
+}
+
+// DDS/KTX2から取り出した、圧縮されたままのミップマップ
+pub(crate) struct CompressedImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: CompressedFormat,
+    // ファイルにsRGBの形式として記録されているかどうか
+    pub srgb: bool,
+    // levels[0]が元の大きさで、1つごとに縦横が半分になる
+    pub levels: Vec<Vec<u8>>,
+}
+
+// DDS/KTX2のファイルかどうか(拡張子で判断する)
+pub(crate) fn is_container(extension: &str) -> bool {
+    extension == "dds" || extension == "ktx2"
+}
+
+pub(crate) fn decode(extension: &str, bytes: &[u8]) -> Result<CompressedImage, DecodeError> {
+    match extension {
+        "dds" => decode_dds(bytes),
+        _ => decode_ktx2(bytes),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|value| u32::from_le_bytes(value.try_into().unwrap()))
+        .ok_or_else(|| DecodeError::Format("unexpected end of file".to_string()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, DecodeError> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|value| u64::from_le_bytes(value.try_into().unwrap()))
+        .ok_or_else(|| DecodeError::Format("unexpected end of file".to_string()))
+}
+
+// 先頭から順に並んだミップマップを切り分ける
+fn split_levels(
+    data: &[u8],
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    level_count: usize,
+) -> Result<Vec<Vec<u8>>, DecodeError> {
+    let mut levels = Vec::with_capacity(level_count);
+    let mut offset: usize = 0;
+    for level in 0..level_count {
+        let size = format.level_size(width, height, level)?;
+        let bytes = offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| DecodeError::Format(format!("mip level {} is truncated", level)))?;
+        levels.push(bytes.to_vec());
+        offset += size;
+    }
+    Ok(levels)
+}
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+fn decode_dds(bytes: &[u8]) -> Result<CompressedImage, DecodeError> {
+    if bytes.get(0..4) != Some(&DDS_MAGIC[..]) {
+        return Err(DecodeError::Format("not a DDS file".to_string()));
+    }
+    // マジックナンバーの後ろに124バイトのヘッダーが続く
+    let header = 4;
+    let flags = read_u32(bytes, header + 4)?;
+    let height = read_u32(bytes, header + 8)?;
+    let width = read_u32(bytes, header + 12)?;
+    let mip_map_count = read_u32(bytes, header + 24)?;
+    let pixel_format_flags = read_u32(bytes, header + 76)?;
+    let four_cc = bytes
+        .get(header + 80..header + 84)
+        .ok_or_else(|| DecodeError::Format("unexpected end of file".to_string()))?;
+    let caps2 = read_u32(bytes, header + 108)?;
+    if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+        return Err(DecodeError::Unsupported(
+            "DDS cubemap or volume texture".to_string(),
+        ));
+    }
+    if pixel_format_flags & DDPF_FOURCC == 0 {
+        return Err(DecodeError::Unsupported(
+            "uncompressed DDS (use PNG instead)".to_string(),
+        ));
+    }
+
+    let mut data_offset = header + 124;
+    let (format, srgb) = match four_cc {
+        b"DXT1" => (CompressedFormat::Bc1, false),
+        b"DXT2" | b"DXT3" => (CompressedFormat::Bc2, false),
+        b"DXT4" | b"DXT5" => (CompressedFormat::Bc3, false),
+        b"ATI1" | b"BC4U" => (CompressedFormat::Bc4, false),
+        b"ATI2" | b"BC5U" => (CompressedFormat::Bc5, false),
+        b"DX10" => {
+            // DX10の拡張ヘッダー(20バイト)
+            let dxgi_format = read_u32(bytes, data_offset)?;
+            let misc_flag = read_u32(bytes, data_offset + 8)?;
+            let array_size = read_u32(bytes, data_offset + 12)?;
+            if misc_flag & DDS_RESOURCE_MISC_TEXTURECUBE != 0 || array_size > 1 {
+                return Err(DecodeError::Unsupported(
+                    "DDS cubemap or texture array".to_string(),
+                ));
+            }
+            data_offset += 20;
+            match dxgi_format {
+                71 => (CompressedFormat::Bc1, false),
+                72 => (CompressedFormat::Bc1, true),
+                74 => (CompressedFormat::Bc2, false),
+                75 => (CompressedFormat::Bc2, true),
+                77 => (CompressedFormat::Bc3, false),
+                78 => (CompressedFormat::Bc3, true),
+                80 => (CompressedFormat::Bc4, false),
+                83 => (CompressedFormat::Bc5, false),
+                95 => (CompressedFormat::Bc6hUnsigned, false),
+                96 => (CompressedFormat::Bc6hSigned, false),
+                98 => (CompressedFormat::Bc7, false),
+                99 => (CompressedFormat::Bc7, true),
+                other => return Err(DecodeError::Unsupported(format!("DXGI format {}", other))),
+            }
+        }
+        other => {
+            return Err(DecodeError::Unsupported(format!(
+                "DDS FourCC {}",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    };
+
+    let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        check_level_count(width, height, mip_map_count)?
+    } else {
+        check_level_count(width, height, 1)?
+    };
+    let data = bytes
+        .get(data_offset..)
+        .ok_or_else(|| DecodeError::Format("unexpected end of file".to_string()))?;
+    let levels = split_levels(data, format, width, height, level_count)?;
+
+    Ok(CompressedImage {
+        width,
+        height,
+        format,
+        srgb,
+        levels,
+    })
+}
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const KTX2_SUPERCOMPRESSION_NONE: u32 = 0;
+const KTX2_SUPERCOMPRESSION_ZLIB: u32 = 3;
+
+fn decode_ktx2(bytes: &[u8]) -> Result<CompressedImage, DecodeError> {
+    if bytes.get(0..12) != Some(&KTX2_IDENTIFIER[..]) {
+        return Err(DecodeError::Format("not a KTX2 file".to_string()));
+    }
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?;
+    let supercompression = read_u32(bytes, 44)?;
+    if depth > 0 || layer_count > 1 || face_count != 1 {
+        return Err(DecodeError::Unsupported(
+            "KTX2 cubemap, array or 3D texture".to_string(),
+        ));
+    }
+    if supercompression != KTX2_SUPERCOMPRESSION_NONE
+        && supercompression != KTX2_SUPERCOMPRESSION_ZLIB
+    {
+        return Err(DecodeError::Unsupported(format!(
+            "KTX2 supercompression scheme {} (BasisLZ and Zstandard are not supported)",
+            supercompression
+        )));
+    }
+
+    // VkFormatの値
+    let (format, srgb) = match vk_format {
+        131 | 133 => (CompressedFormat::Bc1, false),
+        132 | 134 => (CompressedFormat::Bc1, true),
+        135 => (CompressedFormat::Bc2, false),
+        136 => (CompressedFormat::Bc2, true),
+        137 => (CompressedFormat::Bc3, false),
+        138 => (CompressedFormat::Bc3, true),
+        139 => (CompressedFormat::Bc4, false),
+        141 => (CompressedFormat::Bc5, false),
+        143 => (CompressedFormat::Bc6hUnsigned, false),
+        144 => (CompressedFormat::Bc6hSigned, false),
+        145 => (CompressedFormat::Bc7, false),
+        146 => (CompressedFormat::Bc7, true),
+        147 => (CompressedFormat::Etc2Rgb, false),
+        148 => (CompressedFormat::Etc2Rgb, true),
+        149 => (CompressedFormat::Etc2RgbA1, false),
+        150 => (CompressedFormat::Etc2RgbA1, true),
+        151 => (CompressedFormat::Etc2Rgba, false),
+        152 => (CompressedFormat::Etc2Rgba, true),
+        other => return Err(DecodeError::Unsupported(format!("KTX2 VkFormat {}", other))),
+    };
+
+    let level_count = check_level_count(width, height, level_count)?;
+
+    // ヘッダー(48バイト)とインデックス(32バイト)の後ろに、レベルごとの位置が並ぶ
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let entry = 80 + level * 24;
+        let offset = usize::try_from(read_u64(bytes, entry)?).unwrap_or(usize::MAX);
+        let length = usize::try_from(read_u64(bytes, entry + 8)?).unwrap_or(usize::MAX);
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or_else(|| DecodeError::Format(format!("mip level {} is truncated", level)))?;
+        let mut data = if supercompression == KTX2_SUPERCOMPRESSION_ZLIB {
+            inflate::inflate_bytes_zlib(data).map_err(DecodeError::Format)?
+        } else {
+            data.to_vec()
+        };
+        let expected = format.level_size(width, height, level)?;
+        if data.len() < expected {
+            return Err(DecodeError::Format(format!(
+                "mip level {} has {} bytes, expected {}",
+                level,
+                data.len(),
+                expected
+            )));
+        }
+        // 後ろの詰め物は、glCompressedTexImage2Dに渡すサイズに含めない
+        data.truncate(expected);
+        levels.push(data);
+    }
+
+    Ok(CompressedImage {
+        width,
+        height,
+        format,
+        srgb,
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds(width: u32, height: u32, mip_map_count: u32, four_cc: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[0..4].copy_from_slice(DDS_MAGIC);
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&DDSD_MIPMAPCOUNT.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&mip_map_count.to_le_bytes());
+        bytes[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes
+    }
+
+    fn ktx2(width: u32, height: u32, levels: &[(u64, u64)]) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        // vkFormat(BC1_RGB_UNORM)、typeSize、幅、高さ、奥行き、層、面、レベル、超圧縮
+        for value in &[131, 1, width, height, 0, 0, 1, levels.len() as u32, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(80, 0);
+        for &(offset, length) in levels {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn dds_splits_mip_levels() {
+        let mut bytes = dds(8, 4, 4, b"DXT5");
+        // 8x4、4x2、2x1、1x1は、それぞれ2、1、1、1ブロック
+        bytes.extend((0..5 * 16).map(|i| i as u8));
+        let image = decode("dds", &bytes).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc3);
+        assert!(!image.srgb);
+        let sizes: Vec<usize> = image.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![32, 16, 16, 16]);
+        assert_eq!(image.levels[1][0], 32);
+    }
+
+    #[test]
+    fn dds_truncated_header_is_an_error() {
+        let bytes = dds(4, 4, 1, b"DXT1");
+        assert!(decode("dds", &bytes[..120]).is_err());
+        let mut bytes = dds(4, 4, 1, b"DX10");
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(decode("dds", &bytes).is_err());
+    }
+
+    #[test]
+    fn dds_truncated_data_is_an_error() {
+        let mut bytes = dds(4, 4, 1, b"DXT1");
+        bytes.extend_from_slice(&[0; 7]);
+        assert!(decode("dds", &bytes).is_err());
+    }
+
+    #[test]
+    fn dds_too_many_mip_levels_is_an_error() {
+        let mut bytes = dds(1, 1, 40, b"DXT1");
+        bytes.extend_from_slice(&[0; 8 * 40]);
+        assert!(decode("dds", &bytes).is_err());
+        let mut bytes = dds(1, 1, u32::MAX, b"DXT1");
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(decode("dds", &bytes).is_err());
+    }
+
+    #[test]
+    fn dds_huge_dimensions_are_an_error() {
+        let mut bytes = dds(u32::MAX, u32::MAX, 32, b"DXT5");
+        bytes.extend_from_slice(&[0; 16]);
+        assert!(decode("dds", &bytes).is_err());
+        assert!(decode("dds", &dds(0, 4, 1, b"DXT1")).is_err());
+    }
+
+    #[test]
+    fn ktx2_reads_levels() {
+        // ヘッダーとインデックス(80バイト)と、3レベル分の位置(72バイト)の後ろ
+        let mut bytes = ktx2(4, 4, &[(152, 8), (160, 8), (168, 8)]);
+        bytes.extend((0..24).map(|i| i as u8));
+        let image = decode("ktx2", &bytes).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc1);
+        assert_eq!(image.levels.len(), 3);
+        assert_eq!(image.levels[2], (16..24).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn ktx2_invalid_offsets_are_an_error() {
+        let bytes = ktx2(4, 4, &[(u64::MAX, 8)]);
+        assert!(decode("ktx2", &bytes).is_err());
+        let bytes = ktx2(4, 4, &[(80, u64::MAX)]);
+        assert!(decode("ktx2", &bytes).is_err());
+        let bytes = ktx2(1, 1, &[(0, 8); 33]);
+        assert!(decode("ktx2", &bytes).is_err());
+    }
+
+    #[test]
+    fn level_dimensions_stop_at_one() {
+        assert_eq!(level_dimensions(8, 2, 2), (2, 1));
+        assert_eq!(level_dimensions(u32::MAX, 1, 40), (1, 1));
+    }
+}
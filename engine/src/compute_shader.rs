@@ -0,0 +1,111 @@
+use std::ops::{BitOr, Deref};
+use std::path::Path;
+
+use gl::types::{GLbitfield, GLint, GLuint};
+
+use crate::preprocessor::Preprocessor;
+use crate::shader::{Shader, ShaderError, ShaderStage};
+
+// コンピュートシェーダーだけからなるプログラム(OpenGL 4.3以降)
+// uniformやストレージブロックの設定は、Derefを通してShaderのメソッドをそのまま使う
+pub struct ComputeShader {
+    shader: Shader,
+    // シェーダー側のlayout(local_size_x = ...)で宣言したワークグループの大きさ
+    work_group_size: [u32; 3],
+}
+
+impl ComputeShader {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<ComputeShader, ShaderError> {
+        ComputeShader::with_preprocessor(path, &Preprocessor::new())
+    }
+
+    pub fn with_preprocessor<P: AsRef<Path>>(
+        path: P,
+        preprocessor: &Preprocessor,
+    ) -> Result<ComputeShader, ShaderError> {
+        let shader =
+            Shader::from_files(&[(ShaderStage::Compute, path.as_ref())], preprocessor, None)?;
+        Ok(ComputeShader::from_shader(shader))
+    }
+
+    pub fn from_source(code: &str) -> Result<ComputeShader, ShaderError> {
+        let shader =
+            Shader::from_stage_sources(&[(ShaderStage::Compute, code)], &Preprocessor::new())?;
+        Ok(ComputeShader::from_shader(shader))
+    }
+
+    fn from_shader(shader: Shader) -> ComputeShader {
+        let mut size: [GLint; 3] = [0; 3];
+        unsafe {
+            gl::GetProgramiv(shader.id, gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+        }
+
+        ComputeShader {
+            shader,
+            work_group_size: [size[0] as u32, size[1] as u32, size[2] as u32],
+        }
+    }
+
+    pub fn work_group_size(&self) -> [u32; 3] {
+        self.work_group_size
+    }
+
+    // ワークグループの数を指定して実行する
+    pub unsafe fn dispatch(&self, x: u32, y: u32, z: u32) {
+        self.shader.use_program();
+        gl::DispatchCompute(x as GLuint, y as GLuint, z as GLuint);
+    }
+
+    // 処理する要素(画像ならピクセル)の数から、必要なワークグループの数を切り上げて実行する
+    pub unsafe fn dispatch_size(&self, width: u32, height: u32, depth: u32) {
+        let [x, y, z] = self.work_group_size;
+        self.dispatch(
+            width.div_ceil(x.max(1)),
+            height.div_ceil(y.max(1)),
+            depth.div_ceil(z.max(1)),
+        );
+    }
+}
+
+impl Deref for ComputeShader {
+    type Target = Shader;
+
+    fn deref(&self) -> &Shader {
+        &self.shader
+    }
+}
+
+// コンピュートシェーダーが書き込んだ結果を、この後のどの処理から参照するか
+// 例: memory_barrier(MemoryBarrier::SHADER_STORAGE | MemoryBarrier::VERTEX_ATTRIB_ARRAY);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBarrier(pub GLbitfield);
+
+impl MemoryBarrier {
+    pub const VERTEX_ATTRIB_ARRAY: MemoryBarrier =
+        MemoryBarrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: MemoryBarrier = MemoryBarrier(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: MemoryBarrier = MemoryBarrier(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: MemoryBarrier = MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE_ACCESS: MemoryBarrier =
+        MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    pub const COMMAND: MemoryBarrier = MemoryBarrier(gl::COMMAND_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: MemoryBarrier = MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    pub const BUFFER_UPDATE: MemoryBarrier = MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: MemoryBarrier = MemoryBarrier(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const SHADER_STORAGE: MemoryBarrier = MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: MemoryBarrier = MemoryBarrier(gl::ALL_BARRIER_BITS);
+}
+
+impl BitOr for MemoryBarrier {
+    type Output = MemoryBarrier;
+
+    fn bitor(self, other: MemoryBarrier) -> MemoryBarrier {
+        MemoryBarrier(self.0 | other.0)
+    }
+}
+
+pub fn memory_barrier(barrier: MemoryBarrier) {
+    unsafe {
+        gl::MemoryBarrier(barrier.0);
+    }
+}
@@ -0,0 +1,47 @@
+use std::io;
+use std::path::Path;
+
+use crate::shader::ShaderError;
+
+// コンパイル時にバイナリへ埋め込んだシェーダーのソース
+// embed_shaders!マクロで作り、Preprocessor::embeddedに渡して使う
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedShaders {
+    files: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedShaders {
+    pub const fn new(files: &'static [(&'static str, &'static str)]) -> EmbeddedShaders {
+        EmbeddedShaders { files }
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&'static str> {
+        self.files
+            .iter()
+            .find(|(name, _)| Path::new(name) == path)
+            .map(|(_, code)| *code)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &'static str> {
+        self.files.iter().map(|(name, _)| *name)
+    }
+
+    pub(crate) fn read(&self, path: &Path) -> Result<String, ShaderError> {
+        self.get(path)
+            .map(str::to_string)
+            .ok_or_else(|| ShaderError::Io {
+                path: path.to_path_buf(),
+                source: io::Error::new(io::ErrorKind::NotFound, "not embedded in the binary"),
+            })
+    }
+}
+
+// 使う側のクレートのbuild.rsが書き出したOUT_DIR/shaders.rsを埋め込む
+// shaders.rsには、rsc/shader以下の全てのファイルの(相対パス, include_str!(...))の配列を書いておく
+// 例: const SHADERS: EmbeddedShaders = embed_shaders!();
+#[macro_export]
+macro_rules! embed_shaders {
+    () => {
+        $crate::EmbeddedShaders::new(include!(concat!(env!("OUT_DIR"), "/shaders.rs")))
+    };
+}
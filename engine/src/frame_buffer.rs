@@ -1,5 +1,10 @@
 use std::ptr;
 
+use gl::types::{GLenum, GLint};
+
+use crate::gl_object::{track, untrack, GlObjectKind};
+use crate::texture_options::TextureOptions;
+
 pub struct FrameBuffer {
     frame_buffer: u32,
     render_buffer: u32,
@@ -8,6 +13,25 @@ pub struct FrameBuffer {
 
 impl FrameBuffer {
     pub fn new(width: u32, height: u32) -> FrameBuffer {
+        FrameBuffer::with_options(width, height, &TextureOptions::default())
+    }
+
+    // カラーバッファーのテクスチャーのサンプリングの設定を指定して作る
+    // 描画するたびに中身が変わるので、ミップマップは作らない(options.mipmapsは無視する)
+    pub fn with_options(width: u32, height: u32, options: &TextureOptions) -> FrameBuffer {
+        FrameBuffer::with_format(width, height, gl::RGB, options)
+    }
+
+    // カラーバッファーの内部フォーマットを指定して作る
+    // リニアな色のまま描いておき、最後の画面への描画でsRGBに変換するときは
+    // RGBA16Fなど、8ビットより精度の高いフォーマットにすると暗い部分の階調が潰れない
+    // (RGB16Fはカラーバッファーとして使えることが保証されていないので、RGBA16FかR11F_G11F_B10Fにする)
+    pub fn with_format(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        options: &TextureOptions,
+    ) -> FrameBuffer {
         let mut frame_buffer: u32 = 0;
         let mut render_buffer: u32 = 0;
         let mut texture_color_buffer: u32 = 0;
@@ -15,18 +39,19 @@ impl FrameBuffer {
         unsafe {
             // FBOの生成および紐づけ
             gl::GenFramebuffers(1, &mut frame_buffer);
+            track(GlObjectKind::Framebuffer, frame_buffer, "FrameBuffer");
             gl::BindFramebuffer(gl::FRAMEBUFFER, frame_buffer);
 
             // FBOに描画した内容を別のところに貼り付ける際には、このテクスチャを使って描画
             // init a color attachment texture
             gl::GenTextures(1, &mut texture_color_buffer);
+            track(GlObjectKind::Texture, texture_color_buffer, "FrameBuffer");
             gl::BindTexture(gl::TEXTURE_2D, texture_color_buffer);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            options.mipmaps(false).apply_to_texture(gl::TEXTURE_2D);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGB as i32,
+                internal_format as i32,
                 width as i32,
                 height as i32,
                 0,
@@ -53,6 +78,7 @@ impl FrameBuffer {
             // ! デプスバッファー
             // init render buffer object
             gl::GenRenderbuffers(1, &mut render_buffer);
+            track(GlObjectKind::Renderbuffer, render_buffer, "FrameBuffer");
             gl::BindRenderbuffer(gl::RENDERBUFFER, render_buffer);
             // 実際の保存域の確保
             gl::RenderbufferStorage(
@@ -87,9 +113,9 @@ impl FrameBuffer {
         }
 
         FrameBuffer {
-            frame_buffer: frame_buffer,
-            render_buffer: render_buffer,
-            texture_color_buffer: texture_color_buffer,
+            frame_buffer,
+            render_buffer,
+            texture_color_buffer,
         }
     }
 
@@ -113,17 +139,37 @@ impl Drop for FrameBuffer {
         unsafe {
             // ヨーダ記法？
             if 0 != self.frame_buffer {
+                untrack(GlObjectKind::Framebuffer, self.frame_buffer);
                 gl::DeleteFramebuffers(1, &self.frame_buffer);
                 self.frame_buffer = 0;
             }
             if 0 != self.texture_color_buffer {
+                untrack(GlObjectKind::Texture, self.texture_color_buffer);
                 gl::DeleteTextures(1, &self.texture_color_buffer);
                 self.texture_color_buffer = 0;
             }
             if 0 != self.render_buffer {
+                untrack(GlObjectKind::Renderbuffer, self.render_buffer);
                 gl::DeleteRenderbuffers(1, &self.render_buffer);
                 self.render_buffer = 0;
             }
         }
     }
 }
+
+// デフォルトのフレームバッファー(ウィンドウ)がsRGBに対応しているかどうか
+// 対応していれば、gl::Enable(gl::FRAMEBUFFER_SRGB)で書き込むときにリニアからsRGBへ変換される
+// 対応していない場合は、最後のパスのシェーダーでガンマ補正する
+pub fn default_framebuffer_is_srgb() -> bool {
+    let mut encoding: GLint = 0;
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::FRAMEBUFFER,
+            gl::BACK_LEFT,
+            gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            &mut encoding,
+        );
+    }
+    encoding as GLenum == gl::SRGB
+}
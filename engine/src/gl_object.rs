@@ -0,0 +1,116 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use gl::types::GLuint;
+
+// エンジンが作ったOpenGLのオブジェクトの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlObjectKind {
+    Buffer,
+    VertexArray,
+    Texture,
+    Program,
+    Framebuffer,
+    Renderbuffer,
+    Sampler,
+}
+
+impl fmt::Display for GlObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            GlObjectKind::Buffer => "buffer",
+            GlObjectKind::VertexArray => "vertex array",
+            GlObjectKind::Texture => "texture",
+            GlObjectKind::Program => "program",
+            GlObjectKind::Framebuffer => "framebuffer",
+            GlObjectKind::Renderbuffer => "renderbuffer",
+            GlObjectKind::Sampler => "sampler",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 生きているオブジェクトの一覧
+// デバッグビルドでだけ記録し、リリースビルドでは何もしない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveObject {
+    pub kind: GlObjectKind,
+    pub id: GLuint,
+    // 持ち主の型やテクスチャーの名前など、リークしたときに探す手がかり
+    pub owner: String,
+}
+
+static LIVE_OBJECTS: Mutex<Vec<LiveObject>> = Mutex::new(Vec::new());
+
+pub(crate) fn track(kind: GlObjectKind, id: GLuint, owner: &str) {
+    if cfg!(debug_assertions) && 0 != id {
+        LIVE_OBJECTS.lock().unwrap().push(LiveObject {
+            kind,
+            id,
+            owner: owner.to_string(),
+        });
+    }
+}
+
+pub(crate) fn untrack(kind: GlObjectKind, id: GLuint) {
+    if cfg!(debug_assertions) && 0 != id {
+        let mut objects = LIVE_OBJECTS.lock().unwrap();
+        if let Some(index) = objects
+            .iter()
+            .position(|object| object.kind == kind && object.id == id)
+        {
+            objects.swap_remove(index);
+        }
+    }
+}
+
+// 種類ごとの生きているオブジェクトの数
+pub fn live_object_count(kind: GlObjectKind) -> usize {
+    LIVE_OBJECTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|object| object.kind == kind)
+        .count()
+}
+
+pub fn live_objects() -> Vec<LiveObject> {
+    LIVE_OBJECTS.lock().unwrap().clone()
+}
+
+// まだ削除されていないオブジェクトを表示し、その数を返す
+pub fn report_leaks() -> usize {
+    let objects = live_objects();
+    if !objects.is_empty() {
+        println!("warning: {} OpenGL objects were not deleted", objects.len());
+        for object in &objects {
+            println!("  {} {} (owner: {})", object.kind, object.id, object.owner);
+        }
+    }
+    objects.len()
+}
+
+// 破棄されるときにreport_leaksを呼ぶ
+// OpenGLのコンテキストを作った直後に作っておくと、
+// 後から作ったオブジェクトが全て破棄された後、コンテキストより先に破棄される
+pub struct LeakCheck {
+    _private: (),
+}
+
+impl Default for LeakCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeakCheck {
+    pub fn new() -> LeakCheck {
+        LeakCheck { _private: () }
+    }
+}
+
+impl Drop for LeakCheck {
+    fn drop(&mut self) {
+        report_leaks();
+    }
+}
@@ -0,0 +1,1014 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+
+use gl::types::{GLenum, GLint, GLsizei};
+use image::{GenericImageView, ImageDecoder};
+
+use crate::block_decoder::{self, DecodedPixels};
+use crate::compressed_texture::{self, CompressedImage};
+use crate::gl_object::{track, untrack, GlObjectKind};
+use crate::openexr;
+use crate::texture_options::{ColorSpace, TextureOptions};
+
+#[derive(Debug)]
+pub enum ImageError {
+    NotFound {
+        path: PathBuf,
+    },
+    Decode {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    UnsupportedFormat {
+        path: PathBuf,
+        format: String,
+    },
+    Upload {
+        path: PathBuf,
+        code: GLenum,
+    },
+    // キューブマップの面が正方形でないか、他の面と大きさが違う
+    CubemapFace {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        expected: u32,
+    },
+    // テクスチャー配列や3Dテクスチャーの層が、最初の層と大きさが違う
+    LayerSize {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        expected_width: u32,
+        expected_height: u32,
+    },
+    UnknownId {
+        id: String,
+    },
+    UnknownLayer {
+        id: String,
+        layer: String,
+    },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::NotFound { path } => {
+                write!(f, "image file is not found: {}", path.display())
+            }
+            ImageError::Decode { path, source } => {
+                write!(f, "failed to decode image: {}: {}", path.display(), source)
+            }
+            ImageError::UnsupportedFormat { path, format } => write!(
+                f,
+                "unsupported image pixel format: {}: {}",
+                path.display(),
+                format
+            ),
+            ImageError::Upload { path, code } => write!(
+                f,
+                "failed to upload texture: {}: gl error 0x{:x}",
+                path.display(),
+                code
+            ),
+            ImageError::CubemapFace {
+                path,
+                width,
+                height,
+                expected,
+            } => write!(
+                f,
+                "cubemap face must be {}x{}: {}: {}x{}",
+                expected,
+                expected,
+                path.display(),
+                width,
+                height
+            ),
+            ImageError::LayerSize {
+                path,
+                width,
+                height,
+                expected_width,
+                expected_height,
+            } => write!(
+                f,
+                "layer must be {}x{}: {}: {}x{}",
+                expected_width,
+                expected_height,
+                path.display(),
+                width,
+                height
+            ),
+            ImageError::UnknownId { id } => write!(f, "texture is not loaded: id={}", id),
+            ImageError::UnknownLayer { id, layer } => write!(
+                f,
+                "texture array has no such layer: id={}, layer={}",
+                id, layer
+            ),
+        }
+    }
+}
+
+// ファイル形式ごとの読み込み処理が返すエラー
+// パスを付けてImageErrorにする
+#[derive(Debug)]
+pub(crate) enum DecodeError {
+    // ファイルが壊れている
+    Format(String),
+    // 読めるけれど、対応していない形式
+    Unsupported(String),
+}
+
+impl DecodeError {
+    fn into_image_error(self, path: &Path) -> ImageError {
+        match self {
+            DecodeError::Format(message) => {
+                decode_error(path, image::ImageError::FormatError(message))
+            }
+            DecodeError::Unsupported(format) => ImageError::UnsupportedFormat {
+                path: path.to_path_buf(),
+                format,
+            },
+        }
+    }
+}
+
+impl error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ImageError::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// 読み込みに失敗したテクスチャーの代わりに使う市松模様
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkerboard {
+    // テクスチャーの一辺のピクセル数
+    pub size: u32,
+    // 一辺あたりのマス目の数
+    pub cells: u32,
+    pub colors: [[u8; 4]; 2],
+}
+
+impl Default for Checkerboard {
+    // よく目立つマゼンタと黒
+    fn default() -> Self {
+        Checkerboard {
+            size: 64,
+            cells: 8,
+            colors: [[255, 0, 255, 255], [0, 0, 0, 255]],
+        }
+    }
+}
+
+impl Checkerboard {
+    fn pixels(&self) -> Vec<u8> {
+        let cell_size = (self.size / self.cells.max(1)).max(1);
+        let mut pixels = Vec::with_capacity(self.size as usize * self.size as usize * 4);
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let color = self.colors[((x / cell_size + y / cell_size) % 2) as usize];
+                pixels.extend_from_slice(&color);
+            }
+        }
+        pixels
+    }
+}
+
+pub struct ImageManager {
+    image_map: HashMap<String, u32>,
+    // GL_TEXTURE_CUBE_MAPのテクスチャー
+    // 2Dのテクスチャーとはバインドする先が違うので、IDの空間を分けておく
+    cubemap_map: HashMap<String, u32>,
+    // GL_TEXTURE_2D_ARRAYのテクスチャーと、層の名前(並び順が層の番号)
+    array_map: HashMap<String, TextureArray>,
+    // GL_TEXTURE_3Dのテクスチャー
+    volume_map: HashMap<String, u32>,
+    // 設定されていれば、読み込まれていないIDにはこのテクスチャーを返す
+    fallback_texture: Option<u32>,
+}
+
+impl Default for ImageManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageManager {
+    pub fn new() -> ImageManager {
+        ImageManager {
+            image_map: HashMap::new(),
+            cubemap_map: HashMap::new(),
+            array_map: HashMap::new(),
+            volume_map: HashMap::new(),
+            fallback_texture: None,
+        }
+    }
+
+    // 読み込みに失敗したり、読み込んでいないIDを指定したときに使うテクスチャーを設定する
+    // Noneを指定すると、get_texture_idがエラーを返すように戻る
+    // 作れなかった場合は、代わりのテクスチャーがない状態になる
+    pub fn set_fallback(&mut self, checkerboard: Option<Checkerboard>) -> Result<(), ImageError> {
+        if let Some(texture) = self.fallback_texture.take() {
+            delete_texture(texture);
+        }
+        if let Some(checkerboard) = checkerboard {
+            let texture = upload_texture(
+                Path::new("<checkerboard>"),
+                "<checkerboard>",
+                gl::TEXTURE_2D,
+                &[PixelData {
+                    width: checkerboard.size,
+                    height: checkerboard.size,
+                    internal_format: gl::RGBA,
+                    format: gl::RGBA,
+                    type_: gl::UNSIGNED_BYTE,
+                    data: checkerboard.pixels(),
+                }],
+                &TextureOptions::default(),
+            )?;
+            self.fallback_texture = Some(texture);
+        }
+
+        Ok(())
+    }
+
+    pub fn load_image(&mut self, path: &Path, id: &str, vflip: bool) -> Result<(), ImageError> {
+        self.load_image_with_options(path, id, vflip, &TextureOptions::default())
+    }
+
+    // ラップやフィルター、ミップマップの有無、色空間を指定して読み込む
+    // アルベドなどの色の画像はColorSpace::Srgbにすると、シェーダーではリニアな値として読める
+    // .hdrと.exrは浮動小数点数(RGBならRGB16F、RGBAならRGBA32F)、16ビットのPNGはRGBA16などになる
+    // .ddsと.ktx2はブロック圧縮のまま(保存されたミップマップごと)送り、ドライバーが対応していない形式はRGBA8に展開する
+    pub fn load_image_with_options(
+        &mut self,
+        path: &Path,
+        id: &str,
+        vflip: bool,
+        options: &TextureOptions,
+    ) -> Result<(), ImageError> {
+        let texture = match extension(path) {
+            // 圧縮テクスチャーは作るときに向きを決めておくもので、ブロックのまま反転できないのでvflipは無視する
+            Some(extension) if compressed_texture::is_container(&extension) => {
+                let image = decode_compressed(path, &extension)?;
+                upload_compressed(path, id, &image, options)?
+            }
+            _ => {
+                let mut pixels = decode_image(path)?;
+                if vflip {
+                    pixels.flip_vertical();
+                }
+                upload_texture(path, id, gl::TEXTURE_2D, &[pixels], options)?
+            }
+        };
+
+        // 同じIDで読み込み直した場合は、前のテクスチャーを削除する
+        if let Some(old_texture) = self.image_map.insert(id.to_string(), texture) {
+            delete_texture(old_texture);
+        }
+
+        Ok(())
+    }
+
+    // 6枚の画像からキューブマップを作る
+    // 面の順番は+X, -X, +Y, -Y, +Z, -Z(GL_TEXTURE_CUBE_MAP_POSITIVE_Xからの順番)
+    // キューブマップは左上が原点なので、上下反転はしない
+    pub fn load_cubemap(
+        &mut self,
+        paths: &[&Path; 6],
+        id: &str,
+        options: &TextureOptions,
+    ) -> Result<(), ImageError> {
+        let mut faces: Vec<PixelData> = Vec::with_capacity(6);
+        for path in paths.iter() {
+            let face = decode_image(path)?;
+            let expected = faces.first().map_or(face.width, |first| first.width);
+            if face.width != expected || face.height != expected {
+                return Err(ImageError::CubemapFace {
+                    path: path.to_path_buf(),
+                    width: face.width,
+                    height: face.height,
+                    expected,
+                });
+            }
+            faces.push(face);
+        }
+
+        // 面ごとに形式が違ってもよいように、RGBAにそろえる
+        // 1面でもHDRや16ビットの画像があれば、全ての面をその精度にする
+        let type_ = widest_type(&faces);
+        let faces: Vec<PixelData> = faces
+            .iter()
+            .map(|face| {
+                PixelData::from_rgba_f32(face.width, face.height, &face.to_rgba_f32(), type_)
+            })
+            .collect();
+        let texture = upload_texture(paths[0], id, gl::TEXTURE_CUBE_MAP, &faces, options)?;
+        self.insert_cubemap(id, texture);
+
+        Ok(())
+    }
+
+    // 正距円筒図法(横:縦 = 2:1)のパノラマ画像から、一辺face_sizeピクセルのキューブマップを作る
+    // 画像の上端が+Y、中央が-Zの方向になる
+    // .hdrや.exrのパノラマは浮動小数点数のまま(RGBA16F)、16ビットのPNGはRGBA16のキューブマップにする
+    pub fn load_equirectangular_cubemap(
+        &mut self,
+        path: &Path,
+        id: &str,
+        face_size: u32,
+        options: &TextureOptions,
+    ) -> Result<(), ImageError> {
+        let panorama = decode_image(path)?;
+        let faces: Vec<PixelData> = equirectangular_to_cubemap(&panorama, face_size)
+            .iter()
+            .map(|face| PixelData::from_rgba_f32(face_size, face_size, face, panorama.type_))
+            .collect();
+        let texture = upload_texture(path, id, gl::TEXTURE_CUBE_MAP, &faces, options)?;
+        self.insert_cubemap(id, texture);
+
+        Ok(())
+    }
+
+    fn insert_cubemap(&mut self, id: &str, texture: u32) {
+        if let Some(old_texture) = self.cubemap_map.insert(id.to_string(), texture) {
+            delete_texture(old_texture);
+        }
+    }
+
+    // 同じ大きさの画像を重ねて、1つのGL_TEXTURE_2D_ARRAYにする
+    // layersは(層の名前, パス)の並びで、シェーダーではこの順番の番号(0から)で層を選ぶ
+    // 地形のスプラッティングやパラパラ漫画のように、多くの画像を1回のバインドで使うときに
+    pub fn load_texture_array(
+        &mut self,
+        layers: &[(&str, &Path)],
+        id: &str,
+        vflip: bool,
+        options: &TextureOptions,
+    ) -> Result<(), ImageError> {
+        let paths: Vec<&Path> = layers.iter().map(|&(_, path)| path).collect();
+        let pixels = decode_layers(&paths, vflip)?;
+        let texture = upload_texture(paths[0], id, gl::TEXTURE_2D_ARRAY, &pixels, options)?;
+        let texture_array = TextureArray {
+            texture,
+            layers: layers.iter().map(|&(name, _)| name.to_string()).collect(),
+        };
+        if let Some(old) = self.array_map.insert(id.to_string(), texture_array) {
+            delete_texture(old.texture);
+        }
+
+        Ok(())
+    }
+
+    // 同じ大きさの断面の画像を、slicesの順にz方向へ積み重ねてGL_TEXTURE_3Dにする
+    // rの座標は0.0が最初の断面、1.0が最後の断面になる(wrap_rも効く)
+    // 読めるのは断面ごとの画像ファイルだけで、.rawなどのボリュームデータは断面の画像に書き出してから使う
+    pub fn load_volume(
+        &mut self,
+        slices: &[&Path],
+        id: &str,
+        vflip: bool,
+        options: &TextureOptions,
+    ) -> Result<(), ImageError> {
+        let pixels = decode_layers(slices, vflip)?;
+        let texture = upload_texture(slices[0], id, gl::TEXTURE_3D, &pixels, options)?;
+        if let Some(old_texture) = self.volume_map.insert(id.to_string(), texture) {
+            delete_texture(old_texture);
+        }
+
+        Ok(())
+    }
+
+    // テクスチャー配列にも市松模様の代わりはない
+    pub fn get_texture_array_id(&self, id: &str) -> Result<u32, ImageError> {
+        self.array_map
+            .get(id)
+            .map(|texture_array| texture_array.texture)
+            .ok_or_else(|| ImageError::UnknownId { id: id.to_string() })
+    }
+
+    // load_texture_arrayで付けた名前から、シェーダーに渡す層の番号を引く
+    pub fn get_layer(&self, id: &str, layer: &str) -> Result<u32, ImageError> {
+        let texture_array = self
+            .array_map
+            .get(id)
+            .ok_or_else(|| ImageError::UnknownId { id: id.to_string() })?;
+        texture_array
+            .layers
+            .iter()
+            .position(|name| name == layer)
+            .map(|index| index as u32)
+            .ok_or_else(|| ImageError::UnknownLayer {
+                id: id.to_string(),
+                layer: layer.to_string(),
+            })
+    }
+
+    // 層の数(パラパラ漫画のコマ送りなどに使う)
+    pub fn get_layer_count(&self, id: &str) -> Result<u32, ImageError> {
+        self.array_map
+            .get(id)
+            .map(|texture_array| texture_array.layers.len() as u32)
+            .ok_or_else(|| ImageError::UnknownId { id: id.to_string() })
+    }
+
+    pub fn get_volume_id(&self, id: &str) -> Result<u32, ImageError> {
+        self.volume_map
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ImageError::UnknownId { id: id.to_string() })
+    }
+
+    // キューブマップには市松模様の代わりがないので、読み込んでいなければエラーを返す
+    pub fn get_cubemap_id(&self, id: &str) -> Result<u32, ImageError> {
+        self.cubemap_map
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ImageError::UnknownId { id: id.to_string() })
+    }
+
+    pub fn get_texture_id(&self, id: &str) -> Result<u32, ImageError> {
+        self.image_map
+            .get(id)
+            .cloned()
+            .or(self.fallback_texture)
+            .ok_or_else(|| ImageError::UnknownId { id: id.to_string() })
+    }
+}
+
+impl Drop for ImageManager {
+    fn drop(&mut self) {
+        for (_, texture) in self.image_map.drain() {
+            delete_texture(texture);
+        }
+        for (_, texture) in self.cubemap_map.drain() {
+            delete_texture(texture);
+        }
+        for (_, texture_array) in self.array_map.drain() {
+            delete_texture(texture_array.texture);
+        }
+        for (_, texture) in self.volume_map.drain() {
+            delete_texture(texture);
+        }
+        if let Some(texture) = self.fallback_texture.take() {
+            delete_texture(texture);
+        }
+    }
+}
+
+struct TextureArray {
+    texture: u32,
+    layers: Vec<String>,
+}
+
+fn decode_error(path: &Path, source: image::ImageError) -> ImageError {
+    match source {
+        image::ImageError::UnsupportedColor(color) => ImageError::UnsupportedFormat {
+            path: path.to_path_buf(),
+            format: format!("{:?}", color),
+        },
+        source => ImageError::Decode {
+            path: path.to_path_buf(),
+            source,
+        },
+    }
+}
+
+fn io_error(path: &Path, source: io::Error) -> ImageError {
+    decode_error(path, image::ImageError::IoError(source))
+}
+
+// 拡張子で読み方を変え、GPUへ送れる形にする
+fn decode_image(path: &Path) -> Result<PixelData, ImageError> {
+    if !path.exists() {
+        return Err(ImageError::NotFound {
+            path: path.to_path_buf(),
+        });
+    }
+
+    match extension(path).as_deref() {
+        Some("hdr") => decode_hdr(path),
+        Some("exr") => decode_exr(path),
+        Some("png") => match decode_png16(path)? {
+            Some(pixels) => Ok(pixels),
+            None => decode_ldr(path),
+        },
+        _ => decode_ldr(path),
+    }
+}
+
+// テクスチャー配列や3Dテクスチャーの層を読み、最初の層と大きさがそろっているか確かめる
+// 層ごとに形式が違ってもよいように、キューブマップと同じくRGBAにそろえる
+fn decode_layers(paths: &[&Path], vflip: bool) -> Result<Vec<PixelData>, ImageError> {
+    if paths.is_empty() {
+        return Err(ImageError::UnsupportedFormat {
+            path: PathBuf::new(),
+            format: "texture with no layers".to_string(),
+        });
+    }
+
+    let mut layers: Vec<PixelData> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mut layer = decode_image(path)?;
+        if let Some(first) = layers.first() {
+            if layer.width != first.width || layer.height != first.height {
+                return Err(ImageError::LayerSize {
+                    path: path.to_path_buf(),
+                    width: layer.width,
+                    height: layer.height,
+                    expected_width: first.width,
+                    expected_height: first.height,
+                });
+            }
+        }
+        if vflip {
+            layer.flip_vertical();
+        }
+        layers.push(layer);
+    }
+
+    // 16ビットのハイトマップなどを8ビットに丸めないように、一番精度の高い層に合わせる
+    let type_ = widest_type(&layers);
+    Ok(layers
+        .iter()
+        .map(|layer| {
+            PixelData::from_rgba_f32(layer.width, layer.height, &layer.to_rgba_f32(), type_)
+        })
+        .collect())
+}
+
+// 小文字にそろえた拡張子
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+// DDS/KTX2
+fn decode_compressed(path: &Path, extension: &str) -> Result<CompressedImage, ImageError> {
+    if !path.exists() {
+        return Err(ImageError::NotFound {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let bytes = fs::read(path).map_err(|source| io_error(path, source))?;
+    compressed_texture::decode(extension, &bytes).map_err(|error| error.into_image_error(path))
+}
+
+// image::openで読める、1チャンネル8ビットの画像
+fn decode_ldr(path: &Path) -> Result<PixelData, ImageError> {
+    let image = image::open(path).map_err(|source| decode_error(path, source))?;
+    let (internal_format, format) = match image {
+        image::ImageLuma8(_) => (gl::RED, gl::RED),
+        image::ImageLumaA8(_) => (gl::RG, gl::RG),
+        image::ImageRgb8(_) => (gl::RGB, gl::RGB),
+        image::ImageRgba8(_) => (gl::RGBA, gl::RGBA),
+        image::ImageBgr8(_) => (gl::RGB, gl::BGR),
+        image::ImageBgra8(_) => (gl::RGBA, gl::BGRA),
+    };
+
+    Ok(PixelData {
+        width: image.width(),
+        height: image.height(),
+        internal_format,
+        format,
+        type_: gl::UNSIGNED_BYTE,
+        data: image.raw_pixels(),
+    })
+}
+
+// Radiance HDR(RGBE)
+fn decode_hdr(path: &Path) -> Result<PixelData, ImageError> {
+    let file = File::open(path).map_err(|source| io_error(path, source))?;
+    let decoder = image::hdr::HDRDecoder::new(BufReader::new(file))
+        .map_err(|source| decode_error(path, source))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()
+        .map_err(|source| decode_error(path, source))?;
+    let values: Vec<f32> = pixels.iter().flat_map(|pixel| pixel.0.to_vec()).collect();
+
+    Ok(PixelData::from_f32(
+        metadata.width,
+        metadata.height,
+        gl::RGB16F,
+        gl::RGB,
+        &values,
+    ))
+}
+
+fn decode_exr(path: &Path) -> Result<PixelData, ImageError> {
+    let bytes = fs::read(path).map_err(|source| io_error(path, source))?;
+    let image = openexr::decode(&bytes).map_err(|error| error.into_image_error(path))?;
+    let (internal_format, format) = match image.channels {
+        1 => (gl::R32F, gl::RED),
+        3 => (gl::RGB16F, gl::RGB),
+        _ => (gl::RGBA32F, gl::RGBA),
+    };
+
+    Ok(PixelData::from_f32(
+        image.width,
+        image.height,
+        internal_format,
+        format,
+        &image.data,
+    ))
+}
+
+// 16ビットのPNGだけを読む(8ビット以下ならNoneを返す)
+// image::openは16ビットの画像に対応していないので、デコーダーを直接使う
+fn decode_png16(path: &Path) -> Result<Option<PixelData>, ImageError> {
+    let file = File::open(path).map_err(|source| io_error(path, source))?;
+    let decoder = image::png::PNGDecoder::new(BufReader::new(file))
+        .map_err(|source| decode_error(path, source))?;
+    let (internal_format, format) = match decoder.colortype() {
+        image::Gray(16) => (gl::R16, gl::RED),
+        image::GrayA(16) => (gl::RG16, gl::RG),
+        image::RGB(16) => (gl::RGB16, gl::RGB),
+        image::RGBA(16) => (gl::RGBA16, gl::RGBA),
+        _ => return Ok(None),
+    };
+    let (width, height) = decoder.dimensions();
+    let bytes = decoder
+        .read_image()
+        .map_err(|source| decode_error(path, source))?;
+    // PNGはビッグエンディアンなので、GL_UNSIGNED_SHORTとして読めるように並べ直す
+    let data = bytes
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            u16::from_be_bytes([pair[0], pair[1]])
+                .to_ne_bytes()
+                .to_vec()
+        })
+        .collect();
+
+    Ok(Some(PixelData {
+        width: width as u32,
+        height: height as u32,
+        internal_format,
+        format,
+        type_: gl::UNSIGNED_SHORT,
+        data,
+    }))
+}
+
+// キューブマップの各面の画素(RGBA)を、パノラマ画像からバイリニア補間で取り出す
+fn equirectangular_to_cubemap(panorama: &PixelData, face_size: u32) -> Vec<Vec<f32>> {
+    use std::f32::consts::PI;
+
+    let (width, height) = (panorama.width, panorama.height);
+    let rgba = panorama.to_rgba_f32();
+    let sample = |x: i64, y: i64| -> &[f32] {
+        // 横方向は一周しているのでつなげ、縦方向は端で止める
+        let x = x.rem_euclid(width as i64) as usize;
+        let y = y.clamp(0, height as i64 - 1) as usize;
+        let index = (y * width as usize + x) * 4;
+        &rgba[index..index + 4]
+    };
+
+    (0..6)
+        .map(|face| {
+            let mut data = Vec::with_capacity(face_size as usize * face_size as usize * 4);
+            for y in 0..face_size {
+                for x in 0..face_size {
+                    // 面の上の座標(-1.0〜1.0)を、OpenGLのキューブマップの規則で方向に直す
+                    let s = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    let t = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    let (dx, dy, dz) = match face {
+                        0 => (1.0, -t, -s),
+                        1 => (-1.0, -t, s),
+                        2 => (s, 1.0, t),
+                        3 => (s, -1.0, -t),
+                        4 => (s, -t, 1.0),
+                        _ => (-s, -t, -1.0),
+                    };
+                    let length = (dx * dx + dy * dy + dz * dz).sqrt();
+                    let longitude = dx.atan2(-dz);
+                    let latitude = (dy / length).asin();
+
+                    let u = (longitude / (2.0 * PI) + 0.5) * width as f32 - 0.5;
+                    let v = (0.5 - latitude / PI) * height as f32 - 0.5;
+                    let (x0, y0) = (u.floor() as i64, v.floor() as i64);
+                    let (fx, fy) = (u - u.floor(), v - v.floor());
+                    let (p00, p10) = (sample(x0, y0), sample(x0 + 1, y0));
+                    let (p01, p11) = (sample(x0, y0 + 1), sample(x0 + 1, y0 + 1));
+                    for c in 0..4 {
+                        let top = p00[c] + (p10[c] - p00[c]) * fx;
+                        let bottom = p01[c] + (p11[c] - p01[c]) * fx;
+                        data.push(top + (bottom - top) * fy);
+                    }
+                }
+            }
+            data
+        })
+        .collect()
+}
+
+// GPUへ送る前の、デコード済みの画素
+struct PixelData {
+    width: u32,
+    height: u32,
+    internal_format: GLenum,
+    format: GLenum,
+    // 1要素の型(GL_UNSIGNED_BYTE、GL_UNSIGNED_SHORT、GL_FLOAT)
+    type_: GLenum,
+    data: Vec<u8>,
+}
+
+impl PixelData {
+    fn from_f32(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        format: GLenum,
+        values: &[f32],
+    ) -> PixelData {
+        PixelData {
+            width,
+            height,
+            internal_format,
+            format,
+            type_: gl::FLOAT,
+            data: values
+                .iter()
+                .flat_map(|value| value.to_ne_bytes().to_vec())
+                .collect(),
+        }
+    }
+
+    // 1要素の型に合わせて詰め直す
+    // GL_FLOATならRGBA16F、GL_UNSIGNED_SHORTならRGBA16、それ以外は8ビットに丸める
+    fn from_rgba_f32(width: u32, height: u32, values: &[f32], type_: GLenum) -> PixelData {
+        match type_ {
+            gl::FLOAT => PixelData::from_f32(width, height, gl::RGBA16F, gl::RGBA, values),
+            gl::UNSIGNED_SHORT => PixelData {
+                width,
+                height,
+                internal_format: gl::RGBA16,
+                format: gl::RGBA,
+                type_: gl::UNSIGNED_SHORT,
+                data: values
+                    .iter()
+                    .flat_map(|value| {
+                        ((value.clamp(0.0, 1.0) * 65535.0).round() as u16)
+                            .to_ne_bytes()
+                            .to_vec()
+                    })
+                    .collect(),
+            },
+            _ => PixelData {
+                width,
+                height,
+                internal_format: gl::RGBA,
+                format: gl::RGBA,
+                type_: gl::UNSIGNED_BYTE,
+                data: values
+                    .iter()
+                    .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect(),
+            },
+        }
+    }
+
+    fn flip_vertical(&mut self) {
+        let row_size = self.data.len() / self.height.max(1) as usize;
+        let rows: Vec<&[u8]> = self.data.chunks(row_size).rev().collect();
+        self.data = rows.concat();
+    }
+
+    // 1ピクセルごとにRGBAの4つの値へ広げる
+    // 整数の形式は0.0〜1.0に正規化し、グレースケールはRGBへ複製する
+    fn to_rgba_f32(&self) -> Vec<f32> {
+        let values: Vec<f32> = match self.type_ {
+            gl::UNSIGNED_BYTE => self
+                .data
+                .iter()
+                .map(|&value| value as f32 / 255.0)
+                .collect(),
+            gl::UNSIGNED_SHORT => self
+                .data
+                .chunks_exact(2)
+                .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]) as f32 / 65535.0)
+                .collect(),
+            _ => self
+                .data
+                .chunks_exact(4)
+                .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect(),
+        };
+        let channels = match self.format {
+            gl::RED => 1,
+            gl::RG => 2,
+            gl::RGB | gl::BGR => 3,
+            _ => 4,
+        };
+        let bgr = self.format == gl::BGR || self.format == gl::BGRA;
+
+        values
+            .chunks_exact(channels)
+            .flat_map(|pixel| match (channels, bgr) {
+                (1, _) => [pixel[0], pixel[0], pixel[0], 1.0],
+                (2, _) => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                (3, false) => [pixel[0], pixel[1], pixel[2], 1.0],
+                (3, true) => [pixel[2], pixel[1], pixel[0], 1.0],
+                (_, false) => [pixel[0], pixel[1], pixel[2], pixel[3]],
+                (_, true) => [pixel[2], pixel[1], pixel[0], pixel[3]],
+            })
+            .collect()
+    }
+}
+
+// 何枚かの画像を1つのテクスチャーにそろえるときの、一番精度の高い1要素の型
+fn widest_type(images: &[PixelData]) -> GLenum {
+    if images.iter().any(|image| image.type_ == gl::FLOAT) {
+        gl::FLOAT
+    } else if images.iter().any(|image| image.type_ == gl::UNSIGNED_SHORT) {
+        gl::UNSIGNED_SHORT
+    } else {
+        gl::UNSIGNED_BYTE
+    }
+}
+
+// targetがGL_TEXTURE_CUBE_MAPのときは、facesに6面分を+Xから順に渡す
+// GL_TEXTURE_2D_ARRAYとGL_TEXTURE_3Dのときは、facesに同じ形式の層を順に渡す
+fn upload_texture(
+    path: &Path,
+    owner: &str,
+    target: GLenum,
+    faces: &[PixelData],
+    options: &TextureOptions,
+) -> Result<u32, ImageError> {
+    let mut texture = 0;
+
+    unsafe {
+        // 前の処理で残っているエラーを読み捨てて、このアップロードのエラーだけを見る
+        while gl::GetError() != gl::NO_ERROR {}
+
+        gl::GenTextures(1, &mut texture);
+        track(GlObjectKind::Texture, texture, owner);
+        gl::BindTexture(target, texture);
+        options.apply_to_texture(target);
+        // 1行のバイト数が4の倍数でない画像(RGBで幅が奇数など)も崩れないようにする
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        if target == gl::TEXTURE_2D_ARRAY || target == gl::TEXTURE_3D {
+            let first = &faces[0];
+            let data: Vec<u8> = faces
+                .iter()
+                .flat_map(|pixels| pixels.data.iter().cloned())
+                .collect();
+            gl::TexImage3D(
+                target,
+                0,
+                options.internal_format(first.internal_format) as i32,
+                first.width as i32,
+                first.height as i32,
+                faces.len() as i32,
+                0,
+                first.format,
+                first.type_,
+                data.as_ptr() as *const c_void,
+            );
+        } else {
+            for (i, pixels) in faces.iter().enumerate() {
+                let face_target = if target == gl::TEXTURE_CUBE_MAP {
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum
+                } else {
+                    target
+                };
+                gl::TexImage2D(
+                    face_target,
+                    0,
+                    options.internal_format(pixels.internal_format) as i32,
+                    pixels.width as i32,
+                    pixels.height as i32,
+                    0,
+                    pixels.format,
+                    pixels.type_,
+                    pixels.data.as_ptr() as *const c_void,
+                );
+            }
+        }
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        if options.mipmaps {
+            gl::GenerateMipmap(target);
+        }
+        gl::BindTexture(target, 0);
+    }
+
+    check_upload(texture).map_err(|code| ImageError::Upload {
+        path: path.to_path_buf(),
+        code,
+    })
+}
+
+// ブロック圧縮のままGPUへ送る
+// ドライバーが形式に対応していなければ、CPUで展開してから送る
+fn upload_compressed(
+    path: &Path,
+    owner: &str,
+    image: &CompressedImage,
+    options: &TextureOptions,
+) -> Result<u32, ImageError> {
+    // ファイルにsRGBと記録されていれば、optionsの指定がなくてもsRGBとして扱う
+    let options = if image.srgb {
+        options.color_space(ColorSpace::Srgb)
+    } else {
+        *options
+    };
+    let internal_format = image
+        .format
+        .gl_enum(options.color_space == ColorSpace::Srgb);
+    // ファイルに入っているミップマップだけを使う(圧縮された形式にはGenerateMipmapを使えない)
+    let level_count = if options.mipmaps {
+        image.levels.len()
+    } else {
+        1
+    };
+    let mut texture = 0;
+
+    unsafe {
+        while gl::GetError() != gl::NO_ERROR {}
+
+        gl::GenTextures(1, &mut texture);
+        track(GlObjectKind::Texture, texture, owner);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        options
+            .mipmaps(level_count > 1)
+            .apply_to_texture(gl::TEXTURE_2D);
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_MAX_LEVEL,
+            level_count as GLint - 1,
+        );
+        for (level, data) in image.levels.iter().take(level_count).enumerate() {
+            let (level_width, level_height) =
+                compressed_texture::level_dimensions(image.width, image.height, level);
+            gl::CompressedTexImage2D(
+                gl::TEXTURE_2D,
+                level as GLint,
+                internal_format,
+                level_width as GLsizei,
+                level_height as GLsizei,
+                0,
+                data.len() as GLsizei,
+                data.as_ptr() as *const c_void,
+            );
+        }
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+
+    match check_upload(texture) {
+        Ok(texture) => Ok(texture),
+        Err(gl::INVALID_ENUM) => {
+            let (width, height) = (image.width, image.height);
+            let pixels = match block_decoder::decode(image.format, width, height, &image.levels[0])
+            {
+                DecodedPixels::Rgba8(data) => PixelData {
+                    width,
+                    height,
+                    internal_format: gl::RGBA,
+                    format: gl::RGBA,
+                    type_: gl::UNSIGNED_BYTE,
+                    data,
+                },
+                // BC6HはHDRなので、RGBA16Fとして送る
+                DecodedPixels::RgbaF32(values) => {
+                    PixelData::from_f32(width, height, gl::RGBA16F, gl::RGBA, &values)
+                }
+            };
+            upload_texture(path, owner, gl::TEXTURE_2D, &[pixels], &options)
+        }
+        Err(code) => Err(ImageError::Upload {
+            path: path.to_path_buf(),
+            code,
+        }),
+    }
+}
+
+// アップロード中にエラーが起きていれば、テクスチャーを削除してエラーを返す
+fn check_upload(texture: u32) -> Result<u32, GLenum> {
+    let code = unsafe { gl::GetError() };
+    if code != gl::NO_ERROR {
+        delete_texture(texture);
+        return Err(code);
+    }
+    Ok(texture)
+}
+
+fn delete_texture(texture: u32) {
+    untrack(GlObjectKind::Texture, texture);
+    unsafe {
+        gl::DeleteTextures(1, &texture);
+    }
+}
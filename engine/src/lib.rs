@@ -0,0 +1,45 @@
+// シェーダーのuniform設定などのunsafeな関数は、OpenGLのコンテキストが有効なスレッドから呼び出すこと
+#![allow(clippy::missing_safety_doc)]
+
+mod block_decoder;
+mod compressed_texture;
+pub mod compute_shader;
+pub mod embedded;
+pub mod frame_buffer;
+pub mod gl_object;
+pub mod image_manager;
+mod openexr;
+pub mod preprocessor;
+pub mod program_cache;
+pub mod sampler;
+pub mod shader;
+pub mod shader_builder;
+pub mod shader_registry;
+pub mod skybox;
+pub mod storage_buffer;
+pub mod streaming_vertex;
+pub mod texture_options;
+pub mod uniform;
+pub mod uniform_buffer;
+pub mod vertex;
+pub mod vertex_layout;
+
+pub use compute_shader::{memory_barrier, ComputeShader, MemoryBarrier};
+pub use embedded::EmbeddedShaders;
+pub use frame_buffer::{default_framebuffer_is_srgb, FrameBuffer};
+pub use gl_object::{report_leaks, GlObjectKind, LeakCheck, LiveObject};
+pub use image_manager::{Checkerboard, ImageError, ImageManager};
+pub use preprocessor::{Preprocessor, ProcessedSource};
+pub use program_cache::ProgramCache;
+pub use sampler::Sampler;
+pub use shader::{ActiveVariable, Diagnostic, Shader, ShaderError, ShaderStage};
+pub use shader_builder::ShaderBuilder;
+pub use shader_registry::ShaderRegistry;
+pub use skybox::Skybox;
+pub use storage_buffer::{Pod, ShaderStorageBuffer, StorageBlockError};
+pub use streaming_vertex::StreamingVertex;
+pub use texture_options::{ColorSpace, Filter, MinFilter, TextureOptions, Wrap};
+pub use uniform::{ImageAccess, ImageUnit, TextureUnit, Uniform, UniformElement};
+pub use uniform_buffer::{Std140, UniformBlockError, UniformBuffer};
+pub use vertex::{deduplicate_vertices, new_screen_vertex_vec, Primitive, ScreenVertex, Vertex};
+pub use vertex_layout::{AttributeFormat, Normalized, VertexAttribute, VertexIndex, VertexLayout};
@@ -0,0 +1,500 @@
+use std::convert::TryInto;
+
+use crate::image_manager::DecodeError;
+
+// OpenEXRの最低限の読み込み
+// 1パートのスキャンライン形式で、圧縮がNONE/RLE/ZIPS/ZIPのものだけに対応する
+// (PIZなどの圧縮やタイル形式は、書き出すときに設定を変えてもらう)
+
+// 上の行から順に、画素ごとにチャンネルの値を並べたもの
+pub(crate) struct ExrImage {
+    pub width: u32,
+    pub height: u32,
+    // 1(Y)、3(RGB)、4(RGBA)のいずれか
+    pub channels: usize,
+    pub data: Vec<f32>,
+}
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const FLAG_TILED: u32 = 0x200;
+const FLAG_NON_IMAGE: u32 = 0x800;
+const FLAG_MULTIPART: u32 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PixelType {
+    Uint,
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn size(self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            PixelType::Uint | PixelType::Float => 4,
+        }
+    }
+}
+
+struct Channel {
+    name: String,
+    pixel_type: PixelType,
+}
+
+// 入力を先頭から読み進める
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::Format("unexpected end of file".to_string()))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // ヌル終端の文字列
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let rest = &self.bytes[self.position..];
+        let length = rest
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| DecodeError::Format("unterminated string".to_string()))?;
+        let string = String::from_utf8_lossy(&rest[..length]).into_owned();
+        self.position += length + 1;
+        Ok(string)
+    }
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<ExrImage, DecodeError> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err(DecodeError::Format("not an OpenEXR file".to_string()));
+    }
+    let version = reader.u32()?;
+    if version & (FLAG_TILED | FLAG_NON_IMAGE | FLAG_MULTIPART) != 0 {
+        return Err(DecodeError::Unsupported(
+            "tiled, deep or multi-part OpenEXR".to_string(),
+        ));
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _type_name = reader.string()?;
+        let size = reader.i32()?;
+        let mut value = Reader {
+            bytes: reader.take(size.max(0) as usize)?,
+            position: 0,
+        };
+        match name.as_str() {
+            "channels" => loop {
+                let channel_name = value.string()?;
+                if channel_name.is_empty() {
+                    break;
+                }
+                let pixel_type = match value.i32()? {
+                    0 => PixelType::Uint,
+                    1 => PixelType::Half,
+                    2 => PixelType::Float,
+                    other => {
+                        return Err(DecodeError::Format(format!("unknown pixel type {}", other)))
+                    }
+                };
+                // pLinear(1) + 予約(3)
+                value.take(4)?;
+                let (x_sampling, y_sampling) = (value.i32()?, value.i32()?);
+                if x_sampling != 1 || y_sampling != 1 {
+                    return Err(DecodeError::Unsupported("subsampled channels".to_string()));
+                }
+                channels.push(Channel {
+                    name: channel_name,
+                    pixel_type,
+                });
+            },
+            "compression" => compression = Some(value.u8()?),
+            "dataWindow" => {
+                data_window = Some((value.i32()?, value.i32()?, value.i32()?, value.i32()?));
+            }
+            _ => {}
+        }
+    }
+
+    let (x_min, y_min, x_max, y_max) =
+        data_window.ok_or_else(|| DecodeError::Format("dataWindow is missing".to_string()))?;
+    if x_max < x_min || y_max < y_min {
+        return Err(DecodeError::Format("dataWindow is empty".to_string()));
+    }
+    // i32の範囲いっぱいの値でもあふれないように、i64で計算する
+    let width = (i64::from(x_max) - i64::from(x_min) + 1) as usize;
+    let height = (i64::from(y_max) - i64::from(y_min) + 1) as usize;
+    // 1バイトの圧縮データが展開後に何バイトまでになりうるか
+    let (lines_per_block, max_ratio) = match compression {
+        Some(0) => (1, 1),
+        // 2バイトで最大128バイト
+        Some(1) => (1, 64),
+        // deflateの最大の圧縮率
+        Some(2) => (1, 1032),
+        Some(3) => (16, 1032),
+        Some(other) => {
+            return Err(DecodeError::Unsupported(format!(
+                "OpenEXR compression {}",
+                compression_name(other)
+            )))
+        }
+        None => return Err(DecodeError::Format("compression is missing".to_string())),
+    };
+
+    // 取り出すチャンネル(ファイル上の並びはアルファベット順)
+    let find = |name: &str| channels.iter().position(|channel| channel.name == name);
+    let picked: Vec<usize> = match (find("R"), find("G"), find("B"), find("A"), find("Y")) {
+        (Some(r), Some(g), Some(b), Some(a), _) => vec![r, g, b, a],
+        (Some(r), Some(g), Some(b), None, _) => vec![r, g, b],
+        (_, _, _, _, Some(y)) => vec![y],
+        _ => {
+            return Err(DecodeError::Unsupported(
+                "OpenEXR without R/G/B or Y channels".to_string(),
+            ))
+        }
+    };
+
+    // でたらめなdataWindowで巨大なメモリを確保しないように、ファイルの大きさから上限を決める
+    let pixel_size: usize = channels
+        .iter()
+        .map(|channel| channel.pixel_type.size())
+        .sum();
+    let too_large = || {
+        DecodeError::Format(format!(
+            "dataWindow {}x{} is too large for the file",
+            width, height
+        ))
+    };
+    let line_size = pixel_size.checked_mul(width).ok_or_else(too_large)?;
+    let image_size = line_size.checked_mul(height).ok_or_else(too_large)?;
+    let data_size = (bytes.len() - reader.position).saturating_mul(max_ratio);
+    if image_size > data_size || width.saturating_mul(height) > data_size {
+        return Err(too_large());
+    }
+    let block_count = height.div_ceil(lines_per_block);
+    // 行の順番はオフセット表とブロックのy座標で分かるので、表は読み飛ばす
+    for _ in 0..block_count {
+        reader.u64()?;
+    }
+
+    let mut data = vec![0.0; width * height * picked.len()];
+    for _ in 0..block_count {
+        let y = reader.i32()?;
+        let size = reader.i32()?;
+        let block = reader.take(size.max(0) as usize)?;
+        let first_line = y
+            .checked_sub(y_min)
+            .filter(|&line| line >= 0 && (line as usize) < height)
+            .ok_or_else(|| DecodeError::Format(format!("scanline {} is out of range", y)))?
+            as usize;
+        let lines = lines_per_block.min(height - first_line);
+        let expected = line_size * lines;
+        let block = if block.len() == expected {
+            // 圧縮しても小さくならなかったブロックは、そのまま保存されている
+            block.to_vec()
+        } else {
+            match compression {
+                Some(1) => predict_and_interleave(run_length_decode(block)?),
+                Some(2) | Some(3) => predict_and_interleave(
+                    inflate::inflate_bytes_zlib(block).map_err(DecodeError::Format)?,
+                ),
+                _ => block.to_vec(),
+            }
+        };
+        if block.len() != expected {
+            return Err(DecodeError::Format(format!(
+                "scanline block {} has {} bytes, expected {}",
+                y,
+                block.len(),
+                expected
+            )));
+        }
+
+        // 1行の中は、チャンネルごとに幅の数だけ値が並んでいる
+        for line in 0..lines {
+            let mut offset = line * line_size;
+            for (index, channel) in channels.iter().enumerate() {
+                let size = channel.pixel_type.size();
+                if let Some(slot) = picked.iter().position(|&picked| picked == index) {
+                    for x in 0..width {
+                        let bytes = &block[offset + x * size..offset + (x + 1) * size];
+                        let value = match channel.pixel_type {
+                            PixelType::Half => {
+                                half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32()
+                            }
+                            PixelType::Float => f32::from_le_bytes(bytes.try_into().unwrap()),
+                            PixelType::Uint => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+                        };
+                        data[((first_line + line) * width + x) * picked.len() + slot] = value;
+                    }
+                }
+                offset += size * width;
+            }
+        }
+    }
+
+    Ok(ExrImage {
+        width: width as u32,
+        height: height as u32,
+        channels: picked.len(),
+        data,
+    })
+}
+
+fn compression_name(compression: u8) -> String {
+    match compression {
+        4 => "PIZ".to_string(),
+        5 => "PXR24".to_string(),
+        6 => "B44".to_string(),
+        7 => "B44A".to_string(),
+        8 => "DWAA".to_string(),
+        9 => "DWAB".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// 負の数はその数だけそのままのバイトが続き、0以上はその次のバイトを(数 + 1)回繰り返す
+fn run_length_decode(bytes: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let mut output = Vec::new();
+    let mut reader = Reader { bytes, position: 0 };
+    while reader.position < bytes.len() {
+        let count = reader.u8()? as i8;
+        if count < 0 {
+            output.extend_from_slice(reader.take((-(count as i32)) as usize)?);
+        } else {
+            let value = reader.u8()?;
+            output.extend(std::iter::repeat_n(value, count as usize + 1));
+        }
+    }
+    Ok(output)
+}
+
+// 圧縮前にかけられている差分の予測と、前半と後半へのバイトの振り分けを元に戻す
+fn predict_and_interleave(mut bytes: Vec<u8>) -> Vec<u8> {
+    for i in 1..bytes.len() {
+        bytes[i] = bytes[i - 1].wrapping_add(bytes[i]).wrapping_sub(128);
+    }
+    let half = bytes.len().div_ceil(2);
+    let mut output = Vec::with_capacity(bytes.len());
+    for i in 0..half {
+        output.push(bytes[i]);
+        if half + i < bytes.len() {
+            output.push(bytes[half + i]);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(bytes: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(type_name.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        bytes.extend_from_slice(value);
+    }
+
+    // B, G, Rのhalfチャンネルを持つ画像のヘッダー
+    fn header(compression: u8, data_window: [i32; 4]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        let mut channels = Vec::new();
+        for name in &["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&[0; 4]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+        attribute(&mut bytes, "channels", "chlist", &channels);
+        attribute(&mut bytes, "compression", "compression", &[compression]);
+        let window: Vec<u8> = data_window
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        attribute(&mut bytes, "dataWindow", "box2i", &window);
+        bytes.push(0);
+        bytes
+    }
+
+    // 各ブロックの(先頭のy座標, データ)を並べてファイルにする
+    fn file(mut bytes: Vec<u8>, blocks: &[(i32, Vec<u8>)]) -> Vec<u8> {
+        let mut offset = (bytes.len() + blocks.len() * 8) as u64;
+        for (_, data) in blocks {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            offset += 8 + data.len() as u64;
+        }
+        for (y, data) in blocks {
+            bytes.extend_from_slice(&y.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    // 幅widthの行を、B, G, Rの順にhalfで並べる
+    fn scanline(pixels: &[[f32; 3]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for channel in (0..3).rev() {
+            for pixel in pixels {
+                bytes.extend_from_slice(&half::f16::from_f32(pixel[channel]).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    // predict_and_interleaveの逆
+    fn split_and_predict(bytes: &[u8]) -> Vec<u8> {
+        let mut output: Vec<u8> = bytes.iter().step_by(2).cloned().collect();
+        output.extend(bytes.iter().skip(1).step_by(2));
+        for i in (1..output.len()).rev() {
+            output[i] = output[i].wrapping_sub(output[i - 1]).wrapping_add(128);
+        }
+        output
+    }
+
+    fn run_length_encode(bytes: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            let run = bytes[i..]
+                .iter()
+                .take(128)
+                .take_while(|&&byte| byte == bytes[i])
+                .count();
+            if run >= 3 {
+                output.push((run - 1) as u8);
+                output.push(bytes[i]);
+                i += run;
+            } else {
+                output.push((-1i8) as u8);
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+        output
+    }
+
+    // 無圧縮のブロックだけを使ったzlibのデータ
+    fn zlib_stored(bytes: &[u8]) -> Vec<u8> {
+        let mut output = vec![0x78, 0x01, 0x01];
+        output.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(bytes.len() as u16)).to_le_bytes());
+        output.extend_from_slice(bytes);
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in bytes {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        output
+    }
+
+    fn pixels(width: usize, height: usize) -> Vec<[f32; 3]> {
+        (0..width * height)
+            .map(|i| [i as f32, 0.5, if i % 2 == 0 { 1.0 } else { 2.0 }])
+            .collect()
+    }
+
+    fn assert_rgb(image: &ExrImage, expected: &[[f32; 3]]) {
+        assert_eq!(image.channels, 3);
+        let values: Vec<f32> = expected.iter().flat_map(|pixel| pixel.to_vec()).collect();
+        assert_eq!(image.data, values);
+    }
+
+    #[test]
+    fn uncompressed_round_trip() {
+        let expected = pixels(3, 2);
+        let blocks: Vec<(i32, Vec<u8>)> = (0..2)
+            .map(|y| (y, scanline(&expected[y as usize * 3..(y as usize + 1) * 3])))
+            .collect();
+        let image = decode(&file(header(0, [0, 0, 2, 1]), &blocks)).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_rgb(&image, &expected);
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        // 同じ値が続く行は、RLEで小さくなる
+        let expected = vec![[1.0, 0.5, 0.25]; 16 * 2];
+        let blocks: Vec<(i32, Vec<u8>)> = (0..2)
+            .map(|y| {
+                let line = scanline(&expected[y as usize * 16..(y as usize + 1) * 16]);
+                (y, run_length_encode(&split_and_predict(&line)))
+            })
+            .collect();
+        assert!(blocks[0].1.len() < 16 * 6);
+        let image = decode(&file(header(1, [0, 0, 15, 1]), &blocks)).unwrap();
+        assert_rgb(&image, &expected);
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        // ZIPは16行で1ブロック、dataWindowの原点がずれていてもよい
+        let expected = pixels(2, 3);
+        let lines: Vec<u8> = expected.chunks(2).flat_map(scanline).collect();
+        let block = zlib_stored(&split_and_predict(&lines));
+        let image = decode(&file(header(3, [10, 20, 11, 22]), &[(20, block)])).unwrap();
+        assert_eq!((image.width, image.height), (2, 3));
+        assert_rgb(&image, &expected);
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        let expected = pixels(3, 2);
+        let blocks = vec![(0, scanline(&expected[..3])), (1, scanline(&expected[3..]))];
+        let bytes = file(header(0, [0, 0, 2, 1]), &blocks);
+        for length in &[0, 4, 20, bytes.len() - 1] {
+            assert!(decode(&bytes[..*length]).is_err());
+        }
+    }
+
+    #[test]
+    fn huge_data_window_is_an_error() {
+        for window in &[
+            [0, 0, i32::MAX, 0],
+            [i32::MIN, i32::MIN, i32::MAX, i32::MAX],
+            [0, 0, 99_999, 99_999],
+        ] {
+            let bytes = file(header(3, *window), &[(0, vec![0; 16])]);
+            assert!(decode(&bytes).is_err());
+        }
+    }
+}
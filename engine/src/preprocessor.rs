@@ -0,0 +1,268 @@
+use std::path::{Path, PathBuf};
+
+use crate::embedded::EmbeddedShaders;
+use crate::shader::{read_source, ShaderError};
+
+// シェーダーのソースを読み込むときの前処理
+// * #include "common/lighting.glsl" をinclude_dirからの相対パスで展開する
+// * Rust側で指定した#defineを#versionの直後に差し込む
+// 展開後の行が元のどのファイルの何行目かを覚えておき、エラーの行番号を戻せるようにする
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessor {
+    include_dir: Option<PathBuf>,
+    defines: Vec<(String, String)>,
+    embedded: Option<EmbeddedShaders>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Preprocessor {
+        Preprocessor {
+            include_dir: None,
+            defines: Vec::new(),
+            embedded: None,
+        }
+    }
+
+    // 指定しない場合は、読み込むシェーダーのファイルと同じディレクトリを基準にする
+    pub fn include_dir<P: AsRef<Path>>(mut self, include_dir: P) -> Preprocessor {
+        self.include_dir = Some(include_dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Preprocessor {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // ファイルシステムの代わりに、バイナリに埋め込んだソースから読み込む
+    // パスは埋め込んだディレクトリからの相対パスで指定する
+    pub fn embedded(mut self, embedded: EmbeddedShaders) -> Preprocessor {
+        self.embedded = Some(embedded);
+        self
+    }
+
+    pub fn process(&self, path: &Path) -> Result<ProcessedSource, ShaderError> {
+        let code = self.read(path)?;
+        self.process_source(path, &code)
+    }
+
+    fn read(&self, path: &Path) -> Result<String, ShaderError> {
+        match &self.embedded {
+            Some(embedded) => embedded.read(path),
+            None => read_source(path),
+        }
+    }
+
+    // pathはエラーの表示とincludeの基準にだけ使い、ファイルは読み込まない
+    pub fn process_source(&self, path: &Path, code: &str) -> Result<ProcessedSource, ShaderError> {
+        let include_dir = match &self.include_dir {
+            Some(include_dir) => include_dir.clone(),
+            None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+
+        let mut source = ProcessedSource {
+            code: String::new(),
+            files: Vec::new(),
+            line_map: Vec::new(),
+        };
+
+        let has_version = code
+            .lines()
+            .any(|line| line.trim_start().starts_with("#version"));
+        if !has_version {
+            self.push_defines(&mut source);
+        }
+
+        self.expand(path, code, &include_dir, true, &mut source)?;
+
+        Ok(source)
+    }
+
+    fn expand(
+        &self,
+        path: &Path,
+        code: &str,
+        include_dir: &Path,
+        top_level: bool,
+        source: &mut ProcessedSource,
+    ) -> Result<(), ShaderError> {
+        let file_index = source.files.len();
+        source.files.push(path.to_path_buf());
+
+        for (index, line) in code.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let trimmed = line.trim_start();
+
+            if let Some(rest) = trimmed.strip_prefix("#include") {
+                let name = parse_include(rest).ok_or_else(|| ShaderError::Preprocess {
+                    path: path.to_path_buf(),
+                    line: line_number,
+                    message: format!("malformed #include directive: {}", trimmed),
+                })?;
+                let include_path = include_dir.join(name);
+                // 同じファイルは一度だけ展開する(循環したincludeもここで止まる)
+                if !source.files.contains(&include_path) {
+                    let include_code = self.read(&include_path)?;
+                    self.expand(&include_path, &include_code, include_dir, false, source)?;
+                }
+                continue;
+            }
+
+            source.push_line(line, Some((file_index, line_number)));
+
+            if top_level && trimmed.starts_with("#version") {
+                self.push_defines(source);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_defines(&self, source: &mut ProcessedSource) {
+        for (name, value) in &self.defines {
+            source.push_line(&format!("#define {} {}", name, value), None);
+        }
+    }
+}
+
+fn parse_include(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let name = rest.strip_prefix('"')?.strip_suffix('"')?;
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+pub struct ProcessedSource {
+    pub code: String,
+    files: Vec<PathBuf>,
+    // 展開後の行(0始まり) -> (filesの添字, 元の行番号)
+    // #defineのように差し込んだ行はNone
+    line_map: Vec<Option<(usize, u32)>>,
+}
+
+impl ProcessedSource {
+    fn push_line(&mut self, line: &str, location: Option<(usize, u32)>) {
+        self.code.push_str(line);
+        self.code.push('\n');
+        self.line_map.push(location);
+    }
+
+    // 展開に使ったファイルの一覧(先頭が元のファイル)
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // ドライバーが報告した行番号(1始まり)を、元のファイルと行番号に戻す
+    pub fn original_location(&self, line: u32) -> Option<(&Path, u32)> {
+        let index = (line as usize).checked_sub(1)?;
+        let (file_index, original_line) = (*self.line_map.get(index)?)?;
+        Some((self.files[file_index].as_path(), original_line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: &[(&str, &str)] = &[
+        (
+            "main.fs",
+            "#version 330 core\n#include \"lighting.glsl\"\nvoid main() {}\n",
+        ),
+        ("lighting.glsl", "#include \"math.glsl\"\nfloat light;\n"),
+        ("math.glsl", "float pi;\n"),
+        ("a.glsl", "float a;\n#include \"b.glsl\"\n"),
+        ("b.glsl", "#include \"a.glsl\"\nfloat b;\n"),
+        ("broken.fs", "#include \"missing.glsl\"\n"),
+    ];
+
+    fn preprocessor() -> Preprocessor {
+        Preprocessor::new().embedded(EmbeddedShaders::new(FILES))
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let source = preprocessor().process(Path::new("main.fs")).unwrap();
+        assert_eq!(
+            source.code,
+            "#version 330 core\nfloat pi;\nfloat light;\nvoid main() {}\n"
+        );
+        assert_eq!(
+            source.files(),
+            &[
+                PathBuf::from("main.fs"),
+                PathBuf::from("lighting.glsl"),
+                PathBuf::from("math.glsl"),
+            ]
+        );
+    }
+
+    #[test]
+    fn include_cycle_is_expanded_once() {
+        let source = preprocessor().process(Path::new("a.glsl")).unwrap();
+        assert_eq!(source.code, "float a;\nfloat b;\n");
+    }
+
+    #[test]
+    fn missing_include_is_an_error() {
+        match preprocessor().process(Path::new("broken.fs")) {
+            Err(ShaderError::Io { path, .. }) => assert_eq!(path, PathBuf::from("missing.glsl")),
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("missing include should be an error"),
+        }
+    }
+
+    #[test]
+    fn malformed_include_is_an_error() {
+        let result = preprocessor().process_source(Path::new("main.fs"), "#include <math.glsl>\n");
+        match result {
+            Err(ShaderError::Preprocess { line, .. }) => assert_eq!(line, 1),
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("malformed include should be an error"),
+        }
+    }
+
+    #[test]
+    fn defines_follow_version() {
+        let source = preprocessor()
+            .define("SHADOWS", "1")
+            .process(Path::new("main.fs"))
+            .unwrap();
+        let lines: Vec<&str> = source.code.lines().collect();
+        assert_eq!(&lines[..2], &["#version 330 core", "#define SHADOWS 1"]);
+    }
+
+    #[test]
+    fn defines_come_first_without_version() {
+        let source = preprocessor()
+            .define("SHADOWS", "1")
+            .process(Path::new("math.glsl"))
+            .unwrap();
+        assert_eq!(source.code, "#define SHADOWS 1\nfloat pi;\n");
+    }
+
+    #[test]
+    fn maps_lines_to_original_files() {
+        let source = preprocessor()
+            .define("SHADOWS", "1")
+            .process(Path::new("main.fs"))
+            .unwrap();
+        // 1: #version, 2: #define, 3: math.glsl, 4: lighting.glsl, 5: main.fs
+        assert_eq!(source.original_location(1), Some((Path::new("main.fs"), 1)));
+        assert_eq!(source.original_location(2), None);
+        assert_eq!(
+            source.original_location(3),
+            Some((Path::new("math.glsl"), 1))
+        );
+        assert_eq!(
+            source.original_location(4),
+            Some((Path::new("lighting.glsl"), 2))
+        );
+        assert_eq!(source.original_location(5), Some((Path::new("main.fs"), 3)));
+        assert_eq!(source.original_location(0), None);
+        assert_eq!(source.original_location(6), None);
+    }
+}
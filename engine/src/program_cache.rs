@@ -0,0 +1,218 @@
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use crate::preprocessor::ProcessedSource;
+use crate::shader::{context_version, has_extension, ShaderStage};
+
+// リンク済みのプログラムのバイナリをディスクに保存し、次回の起動でコンパイルを省略する
+// キーは前処理後のソースとドライバー(GL_VENDOR/GL_RENDERER/GL_VERSION)から作るので、
+// ソースやドライバーが変わったら自動的に作り直される
+// ドライバーがバイナリを受け付けなかった場合は、通常どおりソースからコンパイルする
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> ProgramCache {
+        ProgramCache {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // ディレクトリごと保存したバイナリを削除する
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    // glGetProgramBinaryが使えない環境(GL 4.1未満でARB_get_program_binaryもない)ではキャッシュしない
+    // NUM_PROGRAM_BINARY_FORMATSも同じ条件で追加された定数なので、対応を確かめてから問い合わせる
+    pub(crate) fn is_supported() -> bool {
+        if !gl::GetProgramBinary::is_loaded() || !gl::ProgramBinary::is_loaded() {
+            return false;
+        }
+        if context_version() < (4, 1) && !has_extension("GL_ARB_get_program_binary") {
+            return false;
+        }
+        let mut formats = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        formats > 0
+    }
+
+    pub(crate) fn key(&self, sources: &[(ShaderStage, PathBuf, ProcessedSource)]) -> u64 {
+        let mut hasher = Fnv1a::new();
+        for &name in &[gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            hasher.write(gl_string(name).as_bytes());
+        }
+        for (stage, _, source) in sources {
+            hasher.write(&stage.gl_enum().to_le_bytes());
+            hasher.write(source.code.as_bytes());
+        }
+        hasher.finish()
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+
+    // 保存したバイナリからプログラムを作る
+    // 見つからないか、ドライバーに拒否された場合はNone
+    pub(crate) fn load(&self, key: u64) -> Option<GLuint> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        if data.len() <= 4 {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        let (format, binary) = data.split_at(4);
+        let format = GLenum::from_le_bytes([format[0], format[1], format[2], format[3]]);
+
+        unsafe {
+            let id = gl::CreateProgram();
+            gl::ProgramBinary(
+                id,
+                format,
+                binary.as_ptr() as *const c_void,
+                binary.len() as GLsizei,
+            );
+
+            let mut success = gl::FALSE as GLint;
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+            if success != gl::TRUE as GLint {
+                // ドライバーの更新などで使えなくなったバイナリは消しておく
+                gl::DeleteProgram(id);
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+
+            Some(id)
+        }
+    }
+
+    // リンクする前にPROGRAM_BINARY_RETRIEVABLE_HINTを設定しておくこと
+    pub(crate) fn store(&self, key: u64, program: GLuint) {
+        let mut length = 0;
+        unsafe {
+            gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+        if length <= 0 {
+            return;
+        }
+
+        let mut format: GLenum = 0;
+        let mut binary = vec![0u8; length as usize];
+        let mut written = 0;
+        unsafe {
+            gl::GetProgramBinary(
+                program,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as *mut c_void,
+            );
+        }
+        if written <= 0 {
+            return;
+        }
+        binary.truncate(written as usize);
+
+        let mut data = Vec::with_capacity(4 + binary.len());
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+
+        let result = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.path(key), data));
+        if let Err(error) = result {
+            println!(
+                "warning: failed to write program binary cache: dir={}, {}",
+                self.dir.display(),
+                error
+            );
+        }
+    }
+}
+
+fn gl_string(name: GLenum) -> String {
+    unsafe {
+        let pointer = gl::GetString(name);
+        if pointer.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(pointer as *const _)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+// キャッシュのファイル名は次回の起動でも同じになる必要があるので、
+// 実装が固定されていないstdのDefaultHasherではなくFNV-1aを使う
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    // 長さも混ぜて、"ab" + "c" と "a" + "bc" を区別する
+    fn write(&mut self, bytes: &[u8]) {
+        self.write_bytes(&(bytes.len() as u64).to_le_bytes());
+        self.write_bytes(bytes);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.write_bytes(bytes);
+        hasher.finish()
+    }
+
+    // FNV-1a(64ビット)の公開されているテストベクター
+    #[test]
+    fn fnv1a_matches_known_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn fnv1a_write_separates_chunks() {
+        let hash = |chunks: &[&[u8]]| {
+            let mut hasher = Fnv1a::new();
+            for chunk in chunks {
+                hasher.write(chunk);
+            }
+            hasher.finish()
+        };
+        assert_ne!(hash(&[b"ab", b"c"]), hash(&[b"a", b"bc"]));
+        assert_eq!(hash(&[b"ab", b"c"]), hash(&[b"ab", b"c"]));
+    }
+}
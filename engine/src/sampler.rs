@@ -0,0 +1,60 @@
+use gl::types::GLuint;
+
+use crate::gl_object::{track, untrack, GlObjectKind};
+use crate::texture_options::TextureOptions;
+use crate::uniform::TextureUnit;
+
+// サンプラーオブジェクト
+// テクスチャーユニットにバインドしている間は、テクスチャー自身の設定より優先される
+// 同じテクスチャーをリピートとクランプの両方で読みたいときなどに使う
+pub struct Sampler {
+    sampler: GLuint,
+}
+
+impl Sampler {
+    pub fn new(options: &TextureOptions) -> Sampler {
+        let mut sampler = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut sampler);
+            track(GlObjectKind::Sampler, sampler, "Sampler");
+            options.apply_to_sampler(sampler);
+        }
+        Sampler { sampler }
+    }
+
+    // サンプラーにはミップマップを作る機能はないので、mipmapsはフィルターの置き換えにだけ使われる
+    pub fn set_options(&self, options: &TextureOptions) {
+        unsafe {
+            options.apply_to_sampler(self.sampler);
+        }
+    }
+
+    pub fn bind(&self, unit: TextureUnit) {
+        unsafe {
+            gl::BindSampler(unit.0, self.sampler);
+        }
+    }
+
+    // テクスチャー自身の設定に戻す
+    pub fn unbind(unit: TextureUnit) {
+        unsafe {
+            gl::BindSampler(unit.0, 0);
+        }
+    }
+
+    pub fn sampler_id(&self) -> GLuint {
+        self.sampler
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            if 0 != self.sampler {
+                untrack(GlObjectKind::Sampler, self.sampler);
+                gl::DeleteSamplers(1, &self.sampler);
+                self.sampler = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,1031 @@
+use cgmath::Array;
+use cgmath::Matrix;
+use gl;
+use gl::types::*;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::mem;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use crate::embedded::EmbeddedShaders;
+use crate::gl_object::{track, untrack, GlObjectKind};
+use crate::preprocessor::{Preprocessor, ProcessedSource};
+use crate::program_cache::ProgramCache;
+use crate::shader_builder::ShaderBuilder;
+use crate::storage_buffer::{ShaderStorageBuffer, StorageBlockError};
+use crate::uniform::{ImageUnit, TextureUnit, Uniform};
+use crate::uniform_buffer::{Std140, UniformBlockError, UniformBuffer};
+
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+
+    // このステージを使うのに必要なOpenGLのバージョン
+    fn required_version(self) -> Option<(GLint, GLint)> {
+        match self {
+            ShaderStage::Vertex | ShaderStage::Fragment => None,
+            ShaderStage::Geometry => Some((3, 2)),
+            ShaderStage::TessControl | ShaderStage::TessEvaluation => Some((4, 0)),
+            ShaderStage::Compute => Some((4, 3)),
+        }
+    }
+
+    // 現在のコンテキストでこのステージが使えるかどうか
+    pub fn is_supported(self) -> bool {
+        match self.required_version() {
+            Some(required) => context_version() >= required,
+            None => true,
+        }
+    }
+}
+
+pub(crate) fn context_version() -> (GLint, GLint) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+// コンテキストが拡張機能(GL_ARB_buffer_storageなど)に対応しているかどうか
+pub(crate) fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count.max(0) as GLuint).any(|i| {
+            let extension = gl::GetStringi(gl::EXTENSIONS, i);
+            !extension.is_null()
+                && CStr::from_ptr(extension as *const c_char).to_bytes() == name.as_bytes()
+        })
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ShaderStage::Vertex => "VERTEX",
+            ShaderStage::TessControl => "TESS_CONTROL",
+            ShaderStage::TessEvaluation => "TESS_EVALUATION",
+            ShaderStage::Geometry => "GEOMETRY",
+            ShaderStage::Fragment => "FRAGMENT",
+            ShaderStage::Compute => "COMPUTE",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// コンパイルエラーのログの1行分
+// ドライバーによって書式が異なるため、行番号と列番号は取れないこともある
+// pathとlineは#includeを展開する前の元のファイルと行番号を指す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub path: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    // 代表的なドライバーのログの書式を解釈する
+    // * Mesa:   "0:12(5): error: ..."
+    // * NVIDIA: "0(12) : error C0000: ..."
+    // * AMD/Intel: "ERROR: 0:12: ..."
+    pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+        log.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Diagnostic::parse_line)
+            .collect()
+    }
+
+    // 行番号を#includeの展開前のファイルと行番号に戻す
+    fn parse_log_with_source(log: &str, source: &ProcessedSource) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostic::parse_log(log);
+        for diagnostic in diagnostics.iter_mut() {
+            if let Some((original_path, original_line)) = diagnostic
+                .line
+                .and_then(|line| source.original_location(line))
+            {
+                diagnostic.path = Some(original_path.to_path_buf());
+                diagnostic.line = Some(original_line);
+            }
+        }
+        diagnostics
+    }
+
+    fn parse_line(line: &str) -> Diagnostic {
+        let mut rest = line;
+        for prefix in &["ERROR: ", "WARNING: "] {
+            if let Some(stripped) = rest.strip_prefix(prefix) {
+                rest = stripped;
+            }
+        }
+
+        let (location, message) = match rest.find(": ") {
+            Some(index) => (&rest[..index], rest[index + 2..].trim()),
+            None => {
+                return Diagnostic {
+                    path: None,
+                    line: None,
+                    column: None,
+                    message: line.to_string(),
+                }
+            }
+        };
+        let location = location.trim();
+
+        let numbers: Vec<u32> = location
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| s.parse().ok())
+            .collect();
+        let is_location = !numbers.is_empty()
+            && location
+                .chars()
+                .all(|c| c.is_ascii_digit() || ":() ".contains(c));
+
+        if !is_location || numbers.len() < 2 {
+            return Diagnostic {
+                path: None,
+                line: None,
+                column: None,
+                message: line.to_string(),
+            };
+        }
+
+        Diagnostic {
+            path: None,
+            line: Some(numbers[1]),
+            column: numbers.get(2).cloned(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            (Some(line), None) => write!(f, "{}: {}", line, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    InvalidSource {
+        stage: ShaderStage,
+        path: PathBuf,
+    },
+    Compile {
+        stage: ShaderStage,
+        path: PathBuf,
+        log: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Link {
+        log: String,
+    },
+    Preprocess {
+        path: PathBuf,
+        line: u32,
+        message: String,
+    },
+    Unsupported {
+        stage: ShaderStage,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, source } => {
+                write!(
+                    f,
+                    "failed to read shader file: {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            ShaderError::InvalidSource { stage, path } => write!(
+                f,
+                "shader source contains a nul byte: type={}, path={}",
+                stage,
+                path.display()
+            ),
+            ShaderError::Compile {
+                stage,
+                path,
+                log,
+                diagnostics,
+            } => {
+                write!(
+                    f,
+                    "failed to compile shader code: type={}, path={}, log=",
+                    stage,
+                    path.display()
+                )?;
+                if diagnostics.is_empty() {
+                    write!(f, "{}", log)
+                } else {
+                    for diagnostic in diagnostics {
+                        writeln!(f, "{}", diagnostic)?;
+                    }
+                    Ok(())
+                }
+            }
+            ShaderError::Link { log } => write!(f, "failed to link shader code: log={}", log),
+            ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            } => write!(
+                f,
+                "failed to preprocess shader code: {}:{}: {}",
+                path.display(),
+                line,
+                message
+            ),
+            ShaderError::Unsupported { stage } => write!(
+                f,
+                "shader stage is not supported by this OpenGL context: type={}",
+                stage
+            ),
+        }
+    }
+}
+
+impl error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ShaderError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// glGetActiveUniform/glGetActiveAttribで取得したアクティブな変数の情報
+// 配列の場合、nameは"uKernel[0]"のように最初の要素の名前になり、sizeに要素数が入る
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveVariable {
+    pub name: String,
+    pub location: GLint,
+    pub type_: GLenum,
+    pub size: GLint,
+}
+
+impl ActiveVariable {
+    pub fn type_name(&self) -> &'static str {
+        match self.type_ {
+            gl::FLOAT => "float",
+            gl::FLOAT_VEC2 => "vec2",
+            gl::FLOAT_VEC3 => "vec3",
+            gl::FLOAT_VEC4 => "vec4",
+            gl::INT => "int",
+            gl::INT_VEC2 => "ivec2",
+            gl::INT_VEC3 => "ivec3",
+            gl::INT_VEC4 => "ivec4",
+            gl::UNSIGNED_INT => "uint",
+            gl::UNSIGNED_INT_VEC2 => "uvec2",
+            gl::UNSIGNED_INT_VEC3 => "uvec3",
+            gl::UNSIGNED_INT_VEC4 => "uvec4",
+            gl::BOOL => "bool",
+            gl::BOOL_VEC2 => "bvec2",
+            gl::BOOL_VEC3 => "bvec3",
+            gl::BOOL_VEC4 => "bvec4",
+            gl::FLOAT_MAT2 => "mat2",
+            gl::FLOAT_MAT3 => "mat3",
+            gl::FLOAT_MAT4 => "mat4",
+            gl::SAMPLER_2D => "sampler2D",
+            gl::SAMPLER_3D => "sampler3D",
+            gl::SAMPLER_CUBE => "samplerCube",
+            gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+            gl::IMAGE_2D => "image2D",
+            gl::IMAGE_3D => "image3D",
+            gl::IMAGE_2D_ARRAY => "image2DArray",
+            _ => "unknown",
+        }
+    }
+}
+
+pub struct Shader {
+    pub id: u32,
+    uniforms: Vec<ActiveVariable>,
+    attributes: Vec<ActiveVariable>,
+    // uniformの名前からロケーションを引くキャッシュ
+    // 存在しない名前は-1を入れておき、警告を出すのは最初の1回だけにする
+    location_cache: RefCell<HashMap<CString, GLint>>,
+    // #includeで読み込んだものも含めた、このプログラムのソースファイル
+    source_files: Vec<PathBuf>,
+}
+
+#[allow(dead_code)]
+impl Shader {
+    pub fn new<P: AsRef<Path>>(vertex_path: P, fragment_path: P) -> Result<Shader, ShaderError> {
+        Shader::with_preprocessor(vertex_path, fragment_path, &Preprocessor::new())
+    }
+
+    pub fn with_preprocessor<P: AsRef<Path>>(
+        vertex_path: P,
+        fragment_path: P,
+        preprocessor: &Preprocessor,
+    ) -> Result<Shader, ShaderError> {
+        Shader::from_files(
+            &[
+                (ShaderStage::Vertex, vertex_path.as_ref()),
+                (ShaderStage::Fragment, fragment_path.as_ref()),
+            ],
+            preprocessor,
+            None,
+        )
+    }
+
+    // リンク済みのバイナリをprogram_cacheに保存し、次回からはコンパイルせずに読み込む
+    pub fn with_program_cache<P: AsRef<Path>>(
+        vertex_path: P,
+        fragment_path: P,
+        preprocessor: &Preprocessor,
+        program_cache: &ProgramCache,
+    ) -> Result<Shader, ShaderError> {
+        Shader::from_files(
+            &[
+                (ShaderStage::Vertex, vertex_path.as_ref()),
+                (ShaderStage::Fragment, fragment_path.as_ref()),
+            ],
+            preprocessor,
+            Some(program_cache),
+        )
+    }
+
+    pub fn with_geometry_shader<P: AsRef<Path>>(
+        vertex_path: P,
+        fragment_path: P,
+        geometry_path: P,
+    ) -> Result<Shader, ShaderError> {
+        ShaderBuilder::new()
+            .vertex(vertex_path)
+            .geometry(geometry_path)
+            .fragment(fragment_path)
+            .build()
+    }
+
+    // ファイルを使わずに、文字列のソースから作る
+    pub fn from_sources(vertex_code: &str, fragment_code: &str) -> Result<Shader, ShaderError> {
+        Shader::from_sources_with_preprocessor(vertex_code, fragment_code, &Preprocessor::new())
+    }
+
+    // #includeはpreprocessorのinclude_dir(指定がなければカレントディレクトリ)から解決する
+    pub fn from_sources_with_preprocessor(
+        vertex_code: &str,
+        fragment_code: &str,
+        preprocessor: &Preprocessor,
+    ) -> Result<Shader, ShaderError> {
+        Shader::from_stage_sources(
+            &[
+                (ShaderStage::Vertex, vertex_code),
+                (ShaderStage::Fragment, fragment_code),
+            ],
+            preprocessor,
+        )
+    }
+
+    // embed_shaders!でバイナリに埋め込んだソースから作る
+    pub fn from_embedded<P: AsRef<Path>>(
+        embedded: EmbeddedShaders,
+        vertex_path: P,
+        fragment_path: P,
+    ) -> Result<Shader, ShaderError> {
+        Shader::with_preprocessor(
+            vertex_path,
+            fragment_path,
+            &Preprocessor::new().embedded(embedded),
+        )
+    }
+
+    pub(crate) fn from_files(
+        stages: &[(ShaderStage, &Path)],
+        preprocessor: &Preprocessor,
+        program_cache: Option<&ProgramCache>,
+    ) -> Result<Shader, ShaderError> {
+        let mut sources = Vec::with_capacity(stages.len());
+        for &(stage, path) in stages {
+            let source = preprocessor.process(path)?;
+            sources.push((stage, path.to_path_buf(), source));
+        }
+        Shader::from_processed_sources(&sources, program_cache)
+    }
+
+    pub(crate) fn from_stage_sources(
+        stages: &[(ShaderStage, &str)],
+        preprocessor: &Preprocessor,
+    ) -> Result<Shader, ShaderError> {
+        let mut sources = Vec::with_capacity(stages.len());
+        for &(stage, code) in stages {
+            let label = PathBuf::from(format!("<{}>", stage));
+            let source = preprocessor.process_source(&label, code)?;
+            sources.push((stage, label, source));
+        }
+        Shader::from_processed_sources(&sources, None)
+    }
+
+    fn from_processed_sources(
+        sources: &[(ShaderStage, PathBuf, ProcessedSource)],
+        program_cache: Option<&ProgramCache>,
+    ) -> Result<Shader, ShaderError> {
+        let mut source_files: Vec<PathBuf> = Vec::new();
+        for (_, _, source) in sources {
+            for file in source.files() {
+                if !source_files.contains(file) {
+                    source_files.push(file.clone());
+                }
+            }
+        }
+
+        let program_cache = program_cache.filter(|_| ProgramCache::is_supported());
+        let cache_key = program_cache.map(|cache| cache.key(sources));
+        if let (Some(cache), Some(key)) = (program_cache, cache_key) {
+            if let Some(id) = cache.load(key) {
+                return Ok(Shader::from_program(id, source_files));
+            }
+        }
+
+        let mut shaders = Vec::with_capacity(sources.len());
+        for (stage, path, source) in sources {
+            match compile_shader(*stage, path, source) {
+                Ok(shader) => shaders.push(shader),
+                Err(error) => {
+                    delete_shaders(&shaders);
+                    return Err(error);
+                }
+            }
+        }
+
+        let result = link_program(&shaders, program_cache.is_some());
+        delete_shaders(&shaders);
+        let id = result?;
+
+        if let (Some(cache), Some(key)) = (program_cache, cache_key) {
+            cache.store(key, id);
+        }
+
+        Ok(Shader::from_program(id, source_files))
+    }
+
+    fn from_program(id: GLuint, source_files: Vec<PathBuf>) -> Shader {
+        let (uniforms, attributes) = unsafe {
+            (
+                active_variables(id, Interface::Uniform),
+                active_variables(id, Interface::Attribute),
+            )
+        };
+
+        let owner = match source_files.first() {
+            Some(path) => format!("Shader({})", path.display()),
+            None => "Shader".to_string(),
+        };
+        track(GlObjectKind::Program, id, &owner);
+
+        let mut location_cache = HashMap::new();
+        for uniform in uniforms.iter().filter(|uniform| uniform.location >= 0) {
+            if let Some(base_name) = uniform.name.strip_suffix("[0]") {
+                location_cache.insert(CString::new(base_name).unwrap(), uniform.location);
+            }
+            location_cache.insert(
+                CString::new(uniform.name.as_str()).unwrap(),
+                uniform.location,
+            );
+        }
+
+        Shader {
+            id,
+            uniforms,
+            attributes,
+            location_cache: RefCell::new(location_cache),
+            source_files,
+        }
+    }
+
+    pub fn source_files(&self) -> &[PathBuf] {
+        &self.source_files
+    }
+
+    pub fn uniforms(&self) -> &[ActiveVariable] {
+        &self.uniforms
+    }
+
+    pub fn attributes(&self) -> &[ActiveVariable] {
+        &self.attributes
+    }
+
+    pub fn uniform_location(&self, name: &CStr) -> GLint {
+        if let Some(&location) = self.location_cache.borrow().get(name) {
+            return location;
+        }
+
+        // 配列の2番目以降の要素など、リフレクションで列挙されない名前はここで問い合わせる
+        let location = unsafe { gl::GetUniformLocation(self.id, name.as_ptr()) };
+        if location < 0 {
+            println!(
+                "warning: uniform is not active in shader program: id={}, name={}",
+                self.id,
+                name.to_string_lossy()
+            );
+        }
+        self.location_cache
+            .borrow_mut()
+            .insert(name.to_owned(), location);
+
+        location
+    }
+
+    pub unsafe fn use_program(&self) {
+        gl::UseProgram(self.id)
+    }
+
+    pub unsafe fn set_bool(&self, name: &CStr, value: bool) {
+        gl::Uniform1i(self.uniform_location(name), value as i32);
+    }
+
+    pub unsafe fn set_int(&self, name: &CStr, value: i32) {
+        gl::Uniform1i(self.uniform_location(name), value);
+    }
+
+    pub unsafe fn set_float(&self, name: &CStr, value: f32) {
+        gl::Uniform1f(self.uniform_location(name), value);
+    }
+
+    pub unsafe fn set_vector3(&self, name: &CStr, value: &Vector3) {
+        gl::Uniform3fv(self.uniform_location(name), 1, value.as_ptr());
+    }
+
+    pub unsafe fn set_vec3(&self, name: &CStr, x: f32, y: f32, z: f32) {
+        gl::Uniform3f(self.uniform_location(name), x, y, z);
+    }
+
+    pub unsafe fn set_mat4(&self, name: &CStr, mat: &Matrix4) {
+        gl::UniformMatrix4fv(self.uniform_location(name), 1, gl::FALSE, mat.as_ptr());
+    }
+
+    // Uniformトレイトを実装した型(cgmathのベクトルや行列、それらのスライスなど)をまとめて扱う
+    pub unsafe fn set_uniform<T: Uniform + ?Sized>(&self, name: &CStr, value: &T) {
+        value.set(self.uniform_location(name));
+    }
+
+    // サンプラーが参照するテクスチャーユニットを明示的に指定する
+    pub unsafe fn set_sampler(&self, name: &CStr, unit: TextureUnit) {
+        self.set_uniform(name, &unit);
+    }
+
+    // イメージ変数(image2Dなど)が参照するイメージユニットを指定する
+    pub unsafe fn set_image(&self, name: &CStr, unit: ImageUnit) {
+        self.set_uniform(name, &unit);
+    }
+
+    // uniformブロックをバッファーのバインディングポイントに結びつける
+    // 結びつける前に、ドライバーが決めたstd140のオフセットとRustの構造体のオフセットを照合する
+    pub fn bind_uniform_block<T: Std140>(
+        &self,
+        block_name: &str,
+        buffer: &UniformBuffer<T>,
+    ) -> Result<(), UniformBlockError> {
+        let cstr_block_name = CString::new(block_name).unwrap();
+
+        unsafe {
+            let index = gl::GetUniformBlockIndex(self.id, cstr_block_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return Err(UniformBlockError::NotFound {
+                    block: block_name.to_string(),
+                });
+            }
+
+            let mut data_size = 0;
+            gl::GetActiveUniformBlockiv(
+                self.id,
+                index,
+                gl::UNIFORM_BLOCK_DATA_SIZE,
+                &mut data_size,
+            );
+            // ブロック全体の大きさは、ドライバーによって16バイト単位に切り上げられることがある
+            let data_size = data_size as usize;
+            if mem::size_of::<T>() < data_size
+                || mem::size_of::<T>() > data_size.next_multiple_of(16)
+            {
+                return Err(UniformBlockError::SizeMismatch {
+                    block: block_name.to_string(),
+                    expected: data_size,
+                    actual: mem::size_of::<T>(),
+                });
+            }
+
+            let mut count = 0;
+            gl::GetActiveUniformBlockiv(
+                self.id,
+                index,
+                gl::UNIFORM_BLOCK_ACTIVE_UNIFORMS,
+                &mut count,
+            );
+            let mut indices = vec![0; count as usize];
+            gl::GetActiveUniformBlockiv(
+                self.id,
+                index,
+                gl::UNIFORM_BLOCK_ACTIVE_UNIFORM_INDICES,
+                indices.as_mut_ptr(),
+            );
+            let indices: Vec<GLuint> = indices.iter().map(|&index| index as GLuint).collect();
+            let mut offsets = vec![0; count as usize];
+            gl::GetActiveUniformsiv(
+                self.id,
+                count,
+                indices.as_ptr(),
+                gl::UNIFORM_OFFSET,
+                offsets.as_mut_ptr(),
+            );
+
+            let members = T::members();
+            let mut matched = vec![false; members.len()];
+            for (&uniform_index, &offset) in indices.iter().zip(offsets.iter()) {
+                let name =
+                    block_member_name(&self.uniforms[uniform_index as usize].name, block_name);
+                match members.iter().position(|&(member, _)| member == name) {
+                    Some(position) => {
+                        matched[position] = true;
+                        let rust_offset = members[position].1;
+                        if rust_offset != offset as usize {
+                            return Err(UniformBlockError::OffsetMismatch {
+                                block: block_name.to_string(),
+                                member: name.to_string(),
+                                expected: offset as usize,
+                                actual: rust_offset,
+                            });
+                        }
+                    }
+                    None => {
+                        return Err(UniformBlockError::MissingMember {
+                            block: block_name.to_string(),
+                            member: name.to_string(),
+                        });
+                    }
+                }
+            }
+            if let Some(position) = matched.iter().position(|&matched| !matched) {
+                return Err(UniformBlockError::UnknownMember {
+                    block: block_name.to_string(),
+                    member: members[position].0.to_string(),
+                });
+            }
+
+            gl::UniformBlockBinding(self.id, index, buffer.binding());
+        }
+
+        Ok(())
+    }
+
+    // シェーダーストレージブロック(buffer Particles { ... };)をバッファーに結びつける
+    // 構造体はstd430のレイアウトに合わせて定義しておくこと
+    pub fn bind_storage_block<T: Copy>(
+        &self,
+        block_name: &str,
+        buffer: &ShaderStorageBuffer<T>,
+    ) -> Result<(), StorageBlockError> {
+        let cstr_block_name = CString::new(block_name).unwrap();
+
+        unsafe {
+            let index = gl::GetProgramResourceIndex(
+                self.id,
+                gl::SHADER_STORAGE_BLOCK,
+                cstr_block_name.as_ptr(),
+            );
+            if index == gl::INVALID_INDEX {
+                return Err(StorageBlockError::NotFound {
+                    block: block_name.to_string(),
+                });
+            }
+            gl::ShaderStorageBlockBinding(self.id, index, buffer.binding());
+        }
+
+        Ok(())
+    }
+
+    // レイアウトの照合を済ませたブロックを、作り直したプログラムに結びつけ直すときに使う
+    pub(crate) fn rebind_uniform_block(&self, block_name: &str, binding: GLuint) {
+        let cstr_block_name = CString::new(block_name).unwrap();
+        unsafe {
+            let index = gl::GetUniformBlockIndex(self.id, cstr_block_name.as_ptr());
+            if index != gl::INVALID_INDEX {
+                gl::UniformBlockBinding(self.id, index, binding);
+            }
+        }
+    }
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe {
+            if 0 != self.id {
+                untrack(GlObjectKind::Program, self.id);
+                gl::DeleteProgram(self.id);
+                self.id = 0;
+            }
+        }
+    }
+}
+
+// uniformブロックのメンバーの名前から、インスタンス名("Block.")と配列の"[0]"を取り除く
+fn block_member_name<'a>(name: &'a str, block_name: &str) -> &'a str {
+    let name = name.strip_suffix("[0]").unwrap_or(name);
+    name.strip_prefix(block_name)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .unwrap_or(name)
+}
+
+#[derive(Clone, Copy)]
+enum Interface {
+    Uniform,
+    Attribute,
+}
+
+unsafe fn active_variables(program: GLuint, interface: Interface) -> Vec<ActiveVariable> {
+    let (count_enum, max_length_enum) = match interface {
+        Interface::Uniform => (gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH),
+        Interface::Attribute => (gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH),
+    };
+
+    let mut count = 0;
+    let mut max_length = 0;
+    gl::GetProgramiv(program, count_enum, &mut count);
+    gl::GetProgramiv(program, max_length_enum, &mut max_length);
+
+    let mut variables = Vec::with_capacity(count as usize);
+    let mut name_buffer = vec![0u8; max_length.max(1) as usize];
+    for index in 0..count as GLuint {
+        let mut length = 0;
+        let mut size = 0;
+        let mut type_ = 0;
+        let get_active = match interface {
+            Interface::Uniform => gl::GetActiveUniform,
+            Interface::Attribute => gl::GetActiveAttrib,
+        };
+        get_active(
+            program,
+            index,
+            name_buffer.len() as GLsizei,
+            &mut length,
+            &mut size,
+            &mut type_,
+            name_buffer.as_mut_ptr() as *mut GLchar,
+        );
+
+        let cstr_name = CString::new(&name_buffer[..length as usize]).unwrap();
+        let location = match interface {
+            Interface::Uniform => gl::GetUniformLocation(program, cstr_name.as_ptr()),
+            Interface::Attribute => gl::GetAttribLocation(program, cstr_name.as_ptr()),
+        };
+
+        variables.push(ActiveVariable {
+            name: cstr_name.to_string_lossy().into_owned(),
+            location,
+            type_,
+            size,
+        });
+    }
+
+    variables
+}
+
+pub(crate) fn read_source(path: &Path) -> Result<String, ShaderError> {
+    let mut code = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut code))
+        .map_err(|source| ShaderError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    Ok(code)
+}
+
+fn compile_shader(
+    stage: ShaderStage,
+    path: &Path,
+    source: &ProcessedSource,
+) -> Result<GLuint, ShaderError> {
+    let cstr_code =
+        CString::new(source.code.as_bytes()).map_err(|_| ShaderError::InvalidSource {
+            stage,
+            path: path.to_path_buf(),
+        })?;
+    if !stage.is_supported() {
+        return Err(ShaderError::Unsupported { stage });
+    }
+
+    unsafe {
+        let shader = gl::CreateShader(stage.gl_enum());
+        gl::ShaderSource(shader, 1, &cstr_code.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        if success != gl::TRUE as GLint {
+            let log = shader_info_log(shader);
+            gl::DeleteShader(shader);
+
+            let diagnostics = Diagnostic::parse_log_with_source(&log, source);
+
+            return Err(ShaderError::Compile {
+                stage,
+                path: path.to_path_buf(),
+                log,
+                diagnostics,
+            });
+        }
+
+        Ok(shader)
+    }
+}
+
+fn link_program(shaders: &[GLuint], retrievable: bool) -> Result<GLuint, ShaderError> {
+    unsafe {
+        let id = gl::CreateProgram();
+        if retrievable {
+            gl::ProgramParameteri(id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+        }
+        for &shader in shaders {
+            gl::AttachShader(id, shader);
+        }
+        gl::LinkProgram(id);
+        for &shader in shaders {
+            gl::DetachShader(id, shader);
+        }
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+        if success != gl::TRUE as GLint {
+            let log = program_info_log(id);
+            gl::DeleteProgram(id);
+            return Err(ShaderError::Link { log });
+        }
+
+        Ok(id)
+    }
+}
+
+fn delete_shaders(shaders: &[GLuint]) {
+    for &shader in shaders {
+        unsafe {
+            gl::DeleteShader(shader);
+        }
+    }
+}
+
+unsafe fn shader_info_log(shader: GLuint) -> String {
+    let mut length = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    gl::GetShaderInfoLog(
+        shader,
+        info_log.len() as GLsizei,
+        &mut written,
+        info_log.as_mut_ptr() as *mut GLchar,
+    );
+    info_log.truncate(written as usize);
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+unsafe fn program_info_log(program: GLuint) -> String {
+    let mut length = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length);
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    gl::GetProgramInfoLog(
+        program,
+        info_log.len() as GLsizei,
+        &mut written,
+        info_log.as_mut_ptr() as *mut GLchar,
+    );
+    info_log.truncate(written as usize);
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mesa_line() {
+        let diagnostics = Diagnostic::parse_log("0:12(5): error: `color' undeclared\n");
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                path: None,
+                line: Some(12),
+                column: Some(5),
+                message: "error: `color' undeclared".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_nvidia_line() {
+        let diagnostics =
+            Diagnostic::parse_log("0(27) : error C1008: undefined variable \"color\"\n");
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                path: None,
+                line: Some(27),
+                column: None,
+                message: "error C1008: undefined variable \"color\"".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_amd_line() {
+        let diagnostics = Diagnostic::parse_log("ERROR: 0:8: 'color' : undeclared identifier\n");
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                path: None,
+                line: Some(8),
+                column: None,
+                message: "'color' : undeclared identifier".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unrecognized_line_keeps_whole_message() {
+        let log = "Fragment shader failed to compile with the following errors:\n\
+                   error: linking failed: missing main\n";
+        let diagnostics = Diagnostic::parse_log(log);
+        assert_eq!(diagnostics.len(), 2);
+        for (diagnostic, line) in diagnostics.iter().zip(log.lines()) {
+            assert_eq!(diagnostic.line, None);
+            assert_eq!(diagnostic.column, None);
+            assert_eq!(diagnostic.message, line.trim());
+        }
+    }
+
+    #[test]
+    fn maps_line_to_included_file() {
+        const FILES: &[(&str, &str)] = &[
+            (
+                "main.fs",
+                "#version 330\n#include \"common.glsl\"\nvoid main() {}\n",
+            ),
+            ("common.glsl", "float a;\nfloat b = c;\n"),
+        ];
+        let source = Preprocessor::new()
+            .embedded(EmbeddedShaders::new(FILES))
+            .process(Path::new("main.fs"))
+            .unwrap();
+        // 展開後の3行目は、common.glslの2行目
+        let diagnostics =
+            Diagnostic::parse_log_with_source("0:3(11): error: `c' undeclared", &source);
+        assert_eq!(diagnostics[0].path, Some(PathBuf::from("common.glsl")));
+        assert_eq!(diagnostics[0].line, Some(2));
+        assert_eq!(diagnostics[0].column, Some(11));
+    }
+}
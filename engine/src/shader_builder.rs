@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+
+use crate::preprocessor::Preprocessor;
+use crate::program_cache::ProgramCache;
+use crate::shader::{Shader, ShaderError, ShaderStage};
+
+// 任意のステージの組み合わせでシェーダープログラムを作る
+// 例: ShaderBuilder::new()
+//         .vertex("terrain.vs")
+//         .tess_control("terrain.tcs")
+//         .tess_evaluation("terrain.tes")
+//         .fragment("terrain.fs")
+//         .build()
+// テッセレーションのステージを使う場合は、Vertex::draw_patchesで描画する
+#[derive(Debug, Clone, Default)]
+pub struct ShaderBuilder {
+    stages: Vec<(ShaderStage, PathBuf)>,
+    preprocessor: Preprocessor,
+    program_cache: Option<ProgramCache>,
+}
+
+impl ShaderBuilder {
+    pub fn new() -> ShaderBuilder {
+        ShaderBuilder {
+            stages: Vec::new(),
+            preprocessor: Preprocessor::new(),
+            program_cache: None,
+        }
+    }
+
+    // 同じステージを2回指定した場合は、後から指定したファイルを使う
+    pub fn stage<P: AsRef<Path>>(mut self, stage: ShaderStage, path: P) -> ShaderBuilder {
+        self.stages.retain(|(s, _)| *s != stage);
+        self.stages.push((stage, path.as_ref().to_path_buf()));
+        self
+    }
+
+    pub fn vertex<P: AsRef<Path>>(self, path: P) -> ShaderBuilder {
+        self.stage(ShaderStage::Vertex, path)
+    }
+
+    pub fn tess_control<P: AsRef<Path>>(self, path: P) -> ShaderBuilder {
+        self.stage(ShaderStage::TessControl, path)
+    }
+
+    pub fn tess_evaluation<P: AsRef<Path>>(self, path: P) -> ShaderBuilder {
+        self.stage(ShaderStage::TessEvaluation, path)
+    }
+
+    pub fn geometry<P: AsRef<Path>>(self, path: P) -> ShaderBuilder {
+        self.stage(ShaderStage::Geometry, path)
+    }
+
+    pub fn fragment<P: AsRef<Path>>(self, path: P) -> ShaderBuilder {
+        self.stage(ShaderStage::Fragment, path)
+    }
+
+    pub fn preprocessor(mut self, preprocessor: Preprocessor) -> ShaderBuilder {
+        self.preprocessor = preprocessor;
+        self
+    }
+
+    pub fn program_cache(mut self, program_cache: ProgramCache) -> ShaderBuilder {
+        self.program_cache = Some(program_cache);
+        self
+    }
+
+    pub fn build(&self) -> Result<Shader, ShaderError> {
+        let stages: Vec<(ShaderStage, &Path)> = self
+            .stages
+            .iter()
+            .map(|(stage, path)| (*stage, path.as_path()))
+            .collect();
+        Shader::from_files(&stages, &self.preprocessor, self.program_cache.as_ref())
+    }
+
+    // ShaderRegistryが再コンパイルのために覚えておく
+    pub(crate) fn into_parts(self) -> (Vec<(ShaderStage, PathBuf)>, Preprocessor) {
+        (self.stages, self.preprocessor)
+    }
+}
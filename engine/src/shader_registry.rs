@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::preprocessor::Preprocessor;
+use crate::program_cache::ProgramCache;
+use crate::shader::{Shader, ShaderError, ShaderStage};
+use crate::shader_builder::ShaderBuilder;
+use crate::uniform_buffer::{Std140, UniformBlockError, UniformBuffer};
+
+// シェーダーのファイルの更新日時を監視して、変更があれば再コンパイルする
+// リンクまで成功したときだけ新しいプログラムに差し替え、失敗したときは直前のプログラムを使い続ける
+pub struct ShaderRegistry {
+    shader_map: HashMap<String, ShaderEntry>,
+    program_cache: Option<ProgramCache>,
+}
+
+struct ShaderEntry {
+    shader: Shader,
+    stages: Vec<(ShaderStage, PathBuf)>,
+    preprocessor: Preprocessor,
+    // #includeされたファイルも含めて監視する
+    watched_files: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    error: Option<ShaderError>,
+    // 再コンパイルしたプログラムにも同じバインディングポイントを設定し直す
+    uniform_blocks: Vec<(String, u32)>,
+}
+
+impl Default for ShaderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderRegistry {
+    pub fn new() -> ShaderRegistry {
+        ShaderRegistry {
+            shader_map: HashMap::new(),
+            program_cache: None,
+        }
+    }
+
+    // これ以降に読み込む(再コンパイルする)シェーダーで、プログラムのバイナリのキャッシュを使う
+    pub fn set_program_cache(&mut self, program_cache: Option<ProgramCache>) {
+        self.program_cache = program_cache;
+    }
+
+    pub fn load<P: AsRef<Path>>(
+        &mut self,
+        id: &str,
+        vertex_path: P,
+        fragment_path: P,
+    ) -> Result<(), ShaderError> {
+        self.load_with_preprocessor(id, vertex_path, fragment_path, Preprocessor::new())
+    }
+
+    pub fn load_with_preprocessor<P: AsRef<Path>>(
+        &mut self,
+        id: &str,
+        vertex_path: P,
+        fragment_path: P,
+        preprocessor: Preprocessor,
+    ) -> Result<(), ShaderError> {
+        self.load_stages(
+            id,
+            vec![
+                (ShaderStage::Vertex, vertex_path.as_ref().to_path_buf()),
+                (ShaderStage::Fragment, fragment_path.as_ref().to_path_buf()),
+            ],
+            preprocessor,
+        )
+    }
+
+    pub fn load_with_geometry_shader<P: AsRef<Path>>(
+        &mut self,
+        id: &str,
+        vertex_path: P,
+        fragment_path: P,
+        geometry_path: P,
+    ) -> Result<(), ShaderError> {
+        self.load_with_builder(
+            id,
+            ShaderBuilder::new()
+                .vertex(vertex_path)
+                .geometry(geometry_path)
+                .fragment(fragment_path),
+        )
+    }
+
+    // テッセレーションなど、任意のステージの組み合わせで読み込む
+    // プログラムのバイナリのキャッシュは、builderではなくset_program_cacheで指定したものを使う
+    pub fn load_with_builder(
+        &mut self,
+        id: &str,
+        builder: ShaderBuilder,
+    ) -> Result<(), ShaderError> {
+        let (stages, preprocessor) = builder.into_parts();
+        self.load_stages(id, stages, preprocessor)
+    }
+
+    fn load_stages(
+        &mut self,
+        id: &str,
+        stages: Vec<(ShaderStage, PathBuf)>,
+        preprocessor: Preprocessor,
+    ) -> Result<(), ShaderError> {
+        let shader = build(&stages, &preprocessor, self.program_cache.as_ref())?;
+        let watched_files = shader.source_files().to_vec();
+        let modified = modified_times(&watched_files);
+
+        let entry = ShaderEntry {
+            shader,
+            stages,
+            preprocessor,
+            watched_files,
+            modified,
+            error: None,
+            uniform_blocks: Vec::new(),
+        };
+        // 同じIDで読み込み直した場合、前のプログラムはここで破棄される
+        self.shader_map.insert(id.to_string(), entry);
+
+        Ok(())
+    }
+
+    // 読み込んでいないIDならNoneを返す
+    pub fn get(&self, id: &str) -> Option<&Shader> {
+        self.shader_map.get(id).map(|entry| &entry.shader)
+    }
+
+    pub fn bind_uniform_block<T: Std140>(
+        &mut self,
+        id: &str,
+        block_name: &str,
+        buffer: &UniformBuffer<T>,
+    ) -> Result<(), UniformBlockError> {
+        let entry = self
+            .shader_map
+            .get_mut(id)
+            .ok_or_else(|| UniformBlockError::ShaderNotLoaded { id: id.to_string() })?;
+        entry.shader.bind_uniform_block(block_name, buffer)?;
+        entry
+            .uniform_blocks
+            .retain(|(name, _)| name.as_str() != block_name);
+        entry
+            .uniform_blocks
+            .push((block_name.to_string(), buffer.binding()));
+        Ok(())
+    }
+
+    // 最後の再コンパイルで発生したエラー
+    // 成功したプログラムに差し替わるとNoneに戻る
+    pub fn error(&self, id: &str) -> Option<&ShaderError> {
+        self.shader_map
+            .get(id)
+            .and_then(|entry| entry.error.as_ref())
+    }
+
+    // ファイルが更新されたシェーダーを再コンパイルし、そのIDを返す
+    // メインループから毎フレーム呼び出すことを想定している
+    pub fn reload_modified(&mut self) -> Vec<String> {
+        let mut reloaded = Vec::new();
+
+        for (id, entry) in self.shader_map.iter_mut() {
+            let modified = modified_times(&entry.watched_files);
+            if modified == entry.modified {
+                continue;
+            }
+            entry.modified = modified;
+
+            match build(
+                &entry.stages,
+                &entry.preprocessor,
+                self.program_cache.as_ref(),
+            ) {
+                Ok(shader) => {
+                    entry.shader = shader;
+                    entry.error = None;
+                    for (block_name, binding) in &entry.uniform_blocks {
+                        entry.shader.rebind_uniform_block(block_name, *binding);
+                    }
+
+                    // includeの構成が変わっていることがあるので、監視するファイルを更新する
+                    if entry.shader.source_files() != entry.watched_files.as_slice() {
+                        entry.watched_files = entry.shader.source_files().to_vec();
+                        entry.modified = modified_times(&entry.watched_files);
+                    }
+                }
+                Err(error) => {
+                    entry.error = Some(error);
+                }
+            }
+            reloaded.push(id.clone());
+        }
+
+        reloaded
+    }
+}
+
+fn build(
+    stages: &[(ShaderStage, PathBuf)],
+    preprocessor: &Preprocessor,
+    program_cache: Option<&ProgramCache>,
+) -> Result<Shader, ShaderError> {
+    let stages: Vec<(ShaderStage, &Path)> = stages
+        .iter()
+        .map(|(stage, path)| (*stage, path.as_path()))
+        .collect();
+    Shader::from_files(&stages, preprocessor, program_cache)
+}
+
+fn modified_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
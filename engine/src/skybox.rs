@@ -0,0 +1,136 @@
+use std::ffi::CStr;
+
+use cgmath::Matrix3;
+use gl::types::{GLboolean, GLenum, GLint, GLuint};
+
+use crate::shader::{context_version, has_extension, Shader, ShaderError};
+use crate::vertex::Vertex;
+
+type Matrix4 = cgmath::Matrix4<f32>;
+
+const VERTEX_SHADER: &str = r#"#version 140
+
+in vec3 iPosition;
+
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec3 Direction;
+
+void main()
+{
+    Direction = iPosition;
+    vec4 position = uProjection * uView * vec4(iPosition, 1.0);
+    // z = wにして、深度が常に一番奥(1.0)になるようにする
+    gl_Position = position.xyww;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 140
+
+in vec3 Direction;
+
+uniform samplerCube uSkybox;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = texture(uSkybox, Direction);
+}
+"#;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SkyboxVertex {
+    position: [f32; 3],
+}
+
+crate::impl_vertex_layout!(SkyboxVertex { position });
+
+// キューブマップを背景として描く
+// 不透明なものを描いた後に呼ぶと、何も描かれていないところだけが塗られる
+pub struct Skybox {
+    vertex: Vertex,
+    shader: Shader,
+}
+
+impl Skybox {
+    pub fn new() -> Result<Skybox, ShaderError> {
+        let shader = Shader::from_sources(VERTEX_SHADER, FRAGMENT_SHADER)?;
+
+        let corners: Vec<SkyboxVertex> = (0..8)
+            .map(|i| SkyboxVertex {
+                position: [
+                    if i & 1 == 0 { -1.0 } else { 1.0 },
+                    if i & 2 == 0 { -1.0 } else { 1.0 },
+                    if i & 4 == 0 { -1.0 } else { 1.0 },
+                ],
+            })
+            .collect();
+        #[rustfmt::skip]
+        let indices: [u16; 36] = [
+            0, 1, 3, 0, 3, 2, // -Z
+            4, 6, 7, 4, 7, 5, // +Z
+            0, 4, 5, 0, 5, 1, // -Y
+            2, 3, 7, 2, 7, 6, // +Y
+            0, 2, 6, 0, 6, 4, // -X
+            1, 5, 7, 1, 7, 3, // +X
+        ];
+        let vertex = Vertex::from_indexed_vertices(&corners, &indices, gl::STATIC_DRAW);
+
+        // キューブマップの面の境目で、隣の面とつなげて補間する
+        // GL 3.2未満でARB_seamless_cube_mapもなければ、境目に継ぎ目が見えるだけなので何もしない
+        if context_version() >= (3, 2) || has_extension("GL_ARB_seamless_cube_map") {
+            unsafe {
+                gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            }
+        }
+
+        Ok(Skybox { vertex, shader })
+    }
+
+    // viewはカメラの行列をそのまま渡してよい(平行移動は取り除く)
+    // cubemapはImageManager::get_cubemap_idで取得したテクスチャー
+    pub fn draw(&self, cubemap: GLuint, view: &Matrix4, projection: &Matrix4) {
+        // 回転だけを残して、カメラが動いても背景が付いてくるようにする
+        let rotation = Matrix4::from(Matrix3::from_cols(
+            view.x.truncate(),
+            view.y.truncate(),
+            view.z.truncate(),
+        ));
+
+        unsafe {
+            // 箱の内側から見るので、カリングは一時的に切る
+            // 深度の設定も書き換えるので、呼び出し元の設定を覚えておいて最後に戻す
+            let culling = gl::IsEnabled(gl::CULL_FACE) == gl::TRUE;
+            let mut depth_func: GLint = 0;
+            gl::GetIntegerv(gl::DEPTH_FUNC, &mut depth_func);
+            let mut depth_mask: GLboolean = gl::TRUE;
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut depth_mask);
+            gl::Disable(gl::CULL_FACE);
+            gl::DepthFunc(gl::LEQUAL);
+            gl::DepthMask(gl::FALSE);
+
+            self.shader.use_program();
+            self.shader.set_mat4(uniform_name(b"uView\0"), &rotation);
+            self.shader
+                .set_mat4(uniform_name(b"uProjection\0"), projection);
+            self.shader.set_int(uniform_name(b"uSkybox\0"), 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, cubemap);
+            self.vertex.draw();
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+
+            gl::DepthMask(depth_mask);
+            gl::DepthFunc(depth_func as GLenum);
+            if culling {
+                gl::Enable(gl::CULL_FACE);
+            }
+        }
+    }
+}
+
+fn uniform_name(name: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(name).expect("failed to get uniform name")
+}
@@ -0,0 +1,165 @@
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+use gl::types::{GLintptr, GLsizeiptr, GLuint};
+
+use crate::gl_object::{track, untrack, GlObjectKind};
+use crate::uniform_buffer::BindingPoints;
+
+static BINDING_POINTS: Mutex<BindingPoints> = Mutex::new(BindingPoints::new());
+
+// GPUから読み戻したバイト列を、そのまま値として使ってよい型
+// どんなビットの並びでも有効な値になる型(f32やu32、それらだけを並べた#[repr(C)]の構造体など)にだけ実装すること
+// bool、char、参照、enumなどは、読み戻した値が不正なビットの並びになりうるので実装してはいけない
+// 例: unsafe impl Pod for Particle {}
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($type_:ty),*) => {
+        $(unsafe impl Pod for $type_ {})*
+    };
+}
+
+impl_pod!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+impl_pod!(
+    cgmath::Vector2<f32>,
+    cgmath::Vector3<f32>,
+    cgmath::Vector4<f32>
+);
+impl_pod!(
+    cgmath::Matrix2<f32>,
+    cgmath::Matrix3<f32>,
+    cgmath::Matrix4<f32>
+);
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+// コンピュートシェーダーから読み書きするシェーダーストレージバッファー(SSBO)
+// Tの配列をそのままGPUへ送るので、#[repr(C)]で定義し、std430のレイアウトに合わせておくこと
+// (vec3の後ろには4バイトのパディングが必要)
+pub struct ShaderStorageBuffer<T: Copy> {
+    buffer: GLuint,
+    binding: GLuint,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> ShaderStorageBuffer<T> {
+    pub fn new(data: &[T]) -> ShaderStorageBuffer<T> {
+        let mut buffer = 0;
+        let binding = BINDING_POINTS.lock().unwrap().allocate();
+
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            track(GlObjectKind::Buffer, buffer, "ShaderStorageBuffer");
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, buffer);
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                gl::DYNAMIC_COPY,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, buffer);
+        }
+
+        ShaderStorageBuffer {
+            buffer,
+            binding,
+            len: data.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    // offset番目の要素から、dataの内容で書き換える
+    pub fn update(&self, offset: usize, data: &[T]) {
+        assert!(
+            offset + data.len() <= self.len,
+            "storage buffer update is out of range: offset={}, count={}, len={}",
+            offset,
+            data.len(),
+            self.len
+        );
+
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.buffer);
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                (offset * mem::size_of::<T>()) as GLintptr,
+                mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+
+    // パーティクルの描画のように、頂点バッファーとして使うときのバッファーID
+    pub fn buffer_id(&self) -> GLuint {
+        self.buffer
+    }
+}
+
+impl<T: Pod> ShaderStorageBuffer<T> {
+    // GPUで書き換えた内容を読み戻す
+    // 読む前にmemory_barrier(MemoryBarrier::BUFFER_UPDATE)を呼んでおくこと
+    pub fn read(&self) -> Vec<T> {
+        // Podは全てのビットが0でも有効な値なので、読み込みに失敗しても未初期化の値は残らない
+        let mut data: Vec<T> = (0..self.len).map(|_| unsafe { mem::zeroed() }).collect();
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.buffer);
+            gl::GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                mem::size_of_val(data.as_slice()) as GLsizeiptr,
+                data.as_mut_ptr() as *mut c_void,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        data
+    }
+}
+
+impl<T: Copy> Drop for ShaderStorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            if 0 != self.buffer {
+                untrack(GlObjectKind::Buffer, self.buffer);
+                gl::DeleteBuffers(1, &self.buffer);
+                self.buffer = 0;
+            }
+        }
+        BINDING_POINTS.lock().unwrap().release(self.binding);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBlockError {
+    NotFound { block: String },
+}
+
+impl fmt::Display for StorageBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageBlockError::NotFound { block } => {
+                write!(f, "shader storage block is not active: block={}", block)
+            }
+        }
+    }
+}
+
+impl error::Error for StorageBlockError {}
@@ -0,0 +1,186 @@
+use std::mem;
+use std::ptr;
+
+use gl::types::{GLbitfield, GLsizei, GLsizeiptr, GLsync};
+
+use crate::shader::{context_version, has_extension};
+use crate::vertex::{Primitive, Vertex};
+use crate::vertex_layout::VertexLayout;
+
+// デバッグ表示の線やCPUで動かすメッシュのように、毎フレーム頂点を詰め直すための頂点バッファー
+// バッファーをsections個の区画に分けて永続的にマップしておき(リングバッファー)、
+// GPUがまだ読んでいる区画には書き込まないようにフェンスで待つ
+// glBufferStorageが使えない環境(GL 4.4未満でARB_buffer_storageもない)やマップに失敗したときは、
+// 1つの区画を毎回確保し直して書き込む
+pub struct StreamingVertex<V: VertexLayout> {
+    vertex: Vertex,
+    // 永続的にマップした領域の先頭(使えない環境ではnull)
+    mapped: *mut V,
+    // 1区画に入る頂点数
+    capacity: usize,
+    // 区画ごとの、最後に描画したコマンドのフェンス
+    fences: Vec<GLsync>,
+    section: usize,
+    len: usize,
+}
+
+impl<V: VertexLayout> StreamingVertex<V> {
+    // 1フレームにcapacity個までの頂点を書き込める
+    // sectionsは同時にGPUが読んでいる可能性のあるフレーム数(3程度)
+    pub fn new(capacity: usize, sections: usize) -> StreamingVertex<V> {
+        assert!(capacity > 0, "streaming buffer capacity must not be zero");
+
+        if buffer_storage_supported() {
+            let sections = sections.max(1);
+            let size = (capacity * sections * mem::size_of::<V>()) as GLsizeiptr;
+            let flags: GLbitfield =
+                gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+            let vertex = StreamingVertex::<V>::allocate(size, || unsafe {
+                gl::BufferStorage(gl::ARRAY_BUFFER, size, ptr::null(), flags);
+            });
+            let mapped = unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, vertex.vbo());
+                let mapped = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, size, flags);
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+                mapped as *mut V
+            };
+            if !mapped.is_null() {
+                return StreamingVertex {
+                    vertex,
+                    mapped,
+                    capacity,
+                    fences: vec![ptr::null(); sections],
+                    section: 0,
+                    len: 0,
+                };
+            }
+            // マップに失敗した領域は作り直せない(glBufferStorageの領域は大きさを変えられない)ので、
+            // 捨ててから毎回確保し直す方法に切り替える
+            drop(vertex);
+            unsafe { while gl::GetError() != gl::NO_ERROR {} }
+        }
+
+        let size = (capacity * mem::size_of::<V>()) as GLsizeiptr;
+        let vertex = StreamingVertex::<V>::allocate(size, || unsafe {
+            gl::BufferData(gl::ARRAY_BUFFER, size, ptr::null(), gl::STREAM_DRAW);
+        });
+        StreamingVertex {
+            vertex,
+            mapped: ptr::null_mut(),
+            capacity,
+            fences: vec![ptr::null(); 1],
+            section: 0,
+            len: 0,
+        }
+    }
+
+    fn allocate<F: FnOnce()>(size: GLsizeiptr, allocate: F) -> Vertex {
+        unsafe {
+            Vertex::with_buffer_storage(
+                size,
+                gl::STREAM_DRAW,
+                &V::attributes(),
+                mem::size_of::<V>() as GLsizei,
+                0,
+                allocate,
+            )
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // デバッグ表示の線などは、Primitive::Linesを指定する
+    pub fn set_primitive(&mut self, primitive: Primitive) {
+        self.vertex.set_primitive(primitive);
+    }
+
+    // 次の区画に頂点を書き込む
+    // その区画をGPUがまだ読んでいれば、読み終わるまで待つ
+    pub fn write(&mut self, vertices: &[V]) {
+        assert!(
+            vertices.len() <= self.capacity,
+            "too many vertices for streaming buffer: count={}, capacity={}",
+            vertices.len(),
+            self.capacity
+        );
+
+        if self.mapped.is_null() {
+            self.vertex.set_vertices(vertices);
+        } else {
+            self.section = (self.section + 1) % self.fences.len();
+            unsafe {
+                wait_fence(&mut self.fences[self.section]);
+                ptr::copy_nonoverlapping(
+                    vertices.as_ptr(),
+                    self.mapped.add(self.section * self.capacity),
+                    vertices.len(),
+                );
+            }
+        }
+        self.len = vertices.len();
+    }
+
+    // 最後にwriteした頂点を描画する
+    pub fn draw(&mut self) {
+        let first = if self.mapped.is_null() {
+            0
+        } else {
+            self.section * self.capacity
+        };
+
+        self.vertex.draw_range(first as i32, self.len as i32);
+        unsafe {
+            if !self.mapped.is_null() {
+                self.fences[self.section] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            }
+        }
+    }
+}
+
+impl<V: VertexLayout> Drop for StreamingVertex<V> {
+    fn drop(&mut self) {
+        unsafe {
+            for fence in self.fences.iter_mut() {
+                if !fence.is_null() {
+                    gl::DeleteSync(*fence);
+                    *fence = ptr::null();
+                }
+            }
+            if !self.mapped.is_null() {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex.vbo());
+                gl::UnmapBuffer(gl::ARRAY_BUFFER);
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+                self.mapped = ptr::null_mut();
+            }
+        }
+    }
+}
+
+// フェンスが通過するまで待ってから削除する
+unsafe fn wait_fence(fence: &mut GLsync) {
+    if fence.is_null() {
+        return;
+    }
+    loop {
+        let result = gl::ClientWaitSync(*fence, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000);
+        if result == gl::ALREADY_SIGNALED
+            || result == gl::CONDITION_SATISFIED
+            || result == gl::WAIT_FAILED
+        {
+            break;
+        }
+    }
+    gl::DeleteSync(*fence);
+    *fence = ptr::null();
+}
+
+// 関数が読み込めただけでは、コンテキストが対応しているとは限らないので、
+// バージョンか拡張機能でglBufferStorageが使えるか確かめる
+fn buffer_storage_supported() -> bool {
+    if !gl::BufferStorage::is_loaded() {
+        return false;
+    }
+    context_version() >= (4, 4) || has_extension("GL_ARB_buffer_storage")
+}
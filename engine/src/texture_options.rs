@@ -0,0 +1,237 @@
+use gl::types::{GLenum, GLfloat, GLint, GLuint};
+
+// 異方性フィルタリング(GL 4.6、それ以前はGL_EXT_texture_filter_anisotropic)
+// glクレートの定義に含まれていないので、ここで定義する
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+// テクスチャー座標が0.0〜1.0の外に出たときの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    // border_colorで塗る
+    ClampToBorder,
+}
+
+impl Wrap {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+// 拡大するときのフィルター
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    // ドット絵のように、ピクセルの境界をくっきり見せる
+    Nearest,
+    Linear,
+}
+
+impl Filter {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+// 縮小するときのフィルター
+// *Mipmap*はミップマップのレベルの選び方で、ミップマップを作らない場合は使えない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            MinFilter::Nearest => gl::NEAREST,
+            MinFilter::Linear => gl::LINEAR,
+            MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    // ミップマップを使わない同等のフィルター
+    pub fn without_mipmap(self) -> MinFilter {
+        match self {
+            MinFilter::Nearest
+            | MinFilter::NearestMipmapNearest
+            | MinFilter::NearestMipmapLinear => MinFilter::Nearest,
+            MinFilter::Linear | MinFilter::LinearMipmapNearest | MinFilter::LinearMipmapLinear => {
+                MinFilter::Linear
+            }
+        }
+    }
+}
+
+// 画素の値がどの色空間で保存されているか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    // 法線マップやラフネスなど、色ではないデータ
+    Linear,
+    // 写真や手描きの色(アルベドなど)
+    // SRGB8_ALPHA8などで保存し、シェーダーで読むときにリニアへ変換させる
+    Srgb,
+}
+
+// テクスチャーのサンプリングの設定
+// 例: ドット絵 TextureOptions::pixel_art()
+//     UI      TextureOptions::default().wrap(Wrap::ClampToEdge)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureOptions {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    pub min_filter: MinFilter,
+    pub mag_filter: Filter,
+    // 1.0で無効、大きくすると斜めから見た面がぼやけにくくなる(ドライバーの上限で切り詰める)
+    pub anisotropy: f32,
+    pub border_color: [f32; 4],
+    // 読み込み時にミップマップを作るかどうか
+    pub mipmaps: bool,
+    // 8ビットのRGB/RGBAの画像だけに効く(サンプラーでは使わない)
+    pub color_space: ColorSpace,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        TextureOptions {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            wrap_r: Wrap::Repeat,
+            min_filter: MinFilter::Linear,
+            mag_filter: Filter::Linear,
+            anisotropy: 1.0,
+            border_color: [0.0, 0.0, 0.0, 0.0],
+            mipmaps: true,
+            color_space: ColorSpace::Linear,
+        }
+    }
+}
+
+impl TextureOptions {
+    // 拡大しても縮小してもぼやけさせない
+    pub fn pixel_art() -> TextureOptions {
+        TextureOptions {
+            min_filter: MinFilter::Nearest,
+            mag_filter: Filter::Nearest,
+            mipmaps: false,
+            ..TextureOptions::default()
+        }
+    }
+
+    // S/T/Rをまとめて設定する
+    pub fn wrap(mut self, wrap: Wrap) -> TextureOptions {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    pub fn filter(mut self, min_filter: MinFilter, mag_filter: Filter) -> TextureOptions {
+        self.min_filter = min_filter;
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    pub fn anisotropy(mut self, anisotropy: f32) -> TextureOptions {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn border_color(mut self, border_color: [f32; 4]) -> TextureOptions {
+        self.border_color = border_color;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> TextureOptions {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn color_space(mut self, color_space: ColorSpace) -> TextureOptions {
+        self.color_space = color_space;
+        self
+    }
+
+    // 色空間に合わせた内部フォーマット
+    // sRGBの内部フォーマットがない形式(REDや浮動小数点数など)はそのまま返す
+    pub(crate) fn internal_format(&self, internal_format: GLenum) -> GLenum {
+        match (self.color_space, internal_format) {
+            (ColorSpace::Srgb, gl::RGB) | (ColorSpace::Srgb, gl::RGB8) => gl::SRGB8,
+            (ColorSpace::Srgb, gl::RGBA) | (ColorSpace::Srgb, gl::RGBA8) => gl::SRGB8_ALPHA8,
+            _ => internal_format,
+        }
+    }
+
+    // ミップマップを作らないのにミップマップを使うフィルターを指定すると
+    // テクスチャーが不完全になって真っ黒になるので、使わないフィルターに置き換える
+    fn effective_min_filter(&self) -> MinFilter {
+        if self.mipmaps {
+            self.min_filter
+        } else {
+            self.min_filter.without_mipmap()
+        }
+    }
+
+    // バインド中のテクスチャーに設定する
+    pub(crate) unsafe fn apply_to_texture(&self, target: GLenum) {
+        self.apply(
+            |name, value| gl::TexParameteri(target, name, value),
+            |name, value| gl::TexParameterfv(target, name, value.as_ptr()),
+        );
+    }
+
+    pub(crate) unsafe fn apply_to_sampler(&self, sampler: GLuint) {
+        self.apply(
+            |name, value| gl::SamplerParameteri(sampler, name, value),
+            |name, value| gl::SamplerParameterfv(sampler, name, value.as_ptr()),
+        );
+    }
+
+    unsafe fn apply<I, F>(&self, mut set_int: I, mut set_float: F)
+    where
+        I: FnMut(GLenum, GLint),
+        F: FnMut(GLenum, &[GLfloat]),
+    {
+        set_int(gl::TEXTURE_WRAP_S, self.wrap_s.gl_enum() as GLint);
+        set_int(gl::TEXTURE_WRAP_T, self.wrap_t.gl_enum() as GLint);
+        set_int(gl::TEXTURE_WRAP_R, self.wrap_r.gl_enum() as GLint);
+        set_int(
+            gl::TEXTURE_MIN_FILTER,
+            self.effective_min_filter().gl_enum() as GLint,
+        );
+        set_int(gl::TEXTURE_MAG_FILTER, self.mag_filter.gl_enum() as GLint);
+        set_float(gl::TEXTURE_BORDER_COLOR, &self.border_color);
+
+        if self.anisotropy > 1.0 {
+            let mut max_anisotropy = 0.0;
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+            if max_anisotropy >= 1.0 {
+                set_float(
+                    TEXTURE_MAX_ANISOTROPY,
+                    &[self.anisotropy.min(max_anisotropy)],
+                );
+            } else {
+                // 異方性フィルタリングに対応していない環境ではINVALID_ENUMになるので読み捨てる
+                gl::GetError();
+            }
+        }
+    }
+}
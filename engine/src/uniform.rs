@@ -0,0 +1,143 @@
+use std::slice;
+
+use gl::types::*;
+
+// Shader::set_uniformで送ることのできる値
+// 配列のuniformにはスライスや配列をそのまま渡せる
+pub trait Uniform {
+    unsafe fn set(&self, location: GLint);
+}
+
+// uniformの配列の要素になれる型
+// 連続したメモリをglUniform*vで一度に送る
+pub trait UniformElement: Sized {
+    unsafe fn set_slice(location: GLint, values: &[Self]);
+}
+
+impl<T: UniformElement> Uniform for T {
+    unsafe fn set(&self, location: GLint) {
+        T::set_slice(location, slice::from_ref(self));
+    }
+}
+
+impl<T: UniformElement> Uniform for [T] {
+    unsafe fn set(&self, location: GLint) {
+        T::set_slice(location, self);
+    }
+}
+
+impl<T: UniformElement, const N: usize> Uniform for [T; N] {
+    unsafe fn set(&self, location: GLint) {
+        T::set_slice(location, self);
+    }
+}
+
+// サンプラーに割り当てるテクスチャーユニットの番号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureUnit(pub GLuint);
+
+impl TextureUnit {
+    pub fn activate(self) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + self.0);
+        }
+    }
+}
+
+impl UniformElement for TextureUnit {
+    unsafe fn set_slice(location: GLint, values: &[Self]) {
+        let units: Vec<GLint> = values.iter().map(|unit| unit.0 as GLint).collect();
+        gl::Uniform1iv(location, units.len() as GLsizei, units.as_ptr());
+    }
+}
+
+// イメージ変数(image2Dなど)に割り当てるイメージユニットの番号
+// コンピュートシェーダーからテクスチャーを直接読み書きするときに使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageUnit(pub GLuint);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl ImageUnit {
+    // formatはシェーダー側のlayout(rgba8)などに合わせる
+    // 配列テクスチャーや3Dテクスチャーは全てのレイヤーを結びつける
+    pub fn bind(self, texture: GLuint, level: GLint, access: ImageAccess, format: GLenum) {
+        let access = match access {
+            ImageAccess::ReadOnly => gl::READ_ONLY,
+            ImageAccess::WriteOnly => gl::WRITE_ONLY,
+            ImageAccess::ReadWrite => gl::READ_WRITE,
+        };
+        unsafe {
+            gl::BindImageTexture(self.0, texture, level, gl::TRUE, 0, access, format);
+        }
+    }
+}
+
+impl UniformElement for ImageUnit {
+    unsafe fn set_slice(location: GLint, values: &[Self]) {
+        let units: Vec<GLint> = values.iter().map(|unit| unit.0 as GLint).collect();
+        gl::Uniform1iv(location, units.len() as GLsizei, units.as_ptr());
+    }
+}
+
+impl UniformElement for bool {
+    unsafe fn set_slice(location: GLint, values: &[Self]) {
+        let values: Vec<GLint> = values.iter().map(|&value| value as GLint).collect();
+        gl::Uniform1iv(location, values.len() as GLsizei, values.as_ptr());
+    }
+}
+
+macro_rules! impl_uniform_vector {
+    ($type_:ty, $scalar:ty, $function:ident) => {
+        impl UniformElement for $type_ {
+            unsafe fn set_slice(location: GLint, values: &[Self]) {
+                gl::$function(
+                    location,
+                    values.len() as GLsizei,
+                    values.as_ptr() as *const $scalar,
+                );
+            }
+        }
+    };
+}
+
+macro_rules! impl_uniform_matrix {
+    ($type_:ty, $function:ident) => {
+        impl UniformElement for $type_ {
+            unsafe fn set_slice(location: GLint, values: &[Self]) {
+                gl::$function(
+                    location,
+                    values.len() as GLsizei,
+                    gl::FALSE,
+                    values.as_ptr() as *const GLfloat,
+                );
+            }
+        }
+    };
+}
+
+impl_uniform_vector!(f32, GLfloat, Uniform1fv);
+impl_uniform_vector!(cgmath::Vector2<f32>, GLfloat, Uniform2fv);
+impl_uniform_vector!(cgmath::Vector3<f32>, GLfloat, Uniform3fv);
+impl_uniform_vector!(cgmath::Vector4<f32>, GLfloat, Uniform4fv);
+impl_uniform_vector!(cgmath::Point2<f32>, GLfloat, Uniform2fv);
+impl_uniform_vector!(cgmath::Point3<f32>, GLfloat, Uniform3fv);
+
+impl_uniform_vector!(i32, GLint, Uniform1iv);
+impl_uniform_vector!(cgmath::Vector2<i32>, GLint, Uniform2iv);
+impl_uniform_vector!(cgmath::Vector3<i32>, GLint, Uniform3iv);
+impl_uniform_vector!(cgmath::Vector4<i32>, GLint, Uniform4iv);
+
+impl_uniform_vector!(u32, GLuint, Uniform1uiv);
+impl_uniform_vector!(cgmath::Vector2<u32>, GLuint, Uniform2uiv);
+impl_uniform_vector!(cgmath::Vector3<u32>, GLuint, Uniform3uiv);
+impl_uniform_vector!(cgmath::Vector4<u32>, GLuint, Uniform4uiv);
+
+impl_uniform_matrix!(cgmath::Matrix2<f32>, UniformMatrix2fv);
+impl_uniform_matrix!(cgmath::Matrix3<f32>, UniformMatrix3fv);
+impl_uniform_matrix!(cgmath::Matrix4<f32>, UniformMatrix4fv);
@@ -0,0 +1,202 @@
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+use gl::types::{GLsizeiptr, GLuint};
+
+use crate::gl_object::{track, untrack, GlObjectKind};
+
+// std140レイアウトのuniformブロックとして、そのままGPUへ送れる構造体
+// #[repr(C)]で定義し、vec3の後ろなどstd140で必要なパディングは自分で入れておくこと
+// members()はGLSL側のメンバー名とRust側のバイトオフセットの組で、
+// Shader::bind_uniform_blockでドライバーが報告するオフセットと照合する
+// impl_std140!マクロで実装するとよい
+pub unsafe trait Std140: Copy {
+    fn members() -> Vec<(&'static str, usize)>;
+}
+
+// 例: impl_std140!(CameraBlock { view: "uView", projection: "uProjection" });
+#[macro_export]
+macro_rules! impl_std140 {
+    ($type_:ty { $($field:ident : $name:literal),* $(,)? }) => {
+        unsafe impl $crate::Std140 for $type_ {
+            fn members() -> Vec<(&'static str, usize)> {
+                vec![$(($name, ::std::mem::offset_of!($type_, $field))),*]
+            }
+        }
+    };
+}
+
+// uniformブロックやシェーダーストレージブロックのバインディングポイントの払い出し
+// バッファーが破棄されたら番号を返却して再利用する
+pub(crate) struct BindingPoints {
+    next: GLuint,
+    free: Vec<GLuint>,
+}
+
+impl BindingPoints {
+    pub(crate) const fn new() -> BindingPoints {
+        BindingPoints {
+            next: 0,
+            free: Vec::new(),
+        }
+    }
+
+    pub(crate) fn allocate(&mut self) -> GLuint {
+        match self.free.pop() {
+            Some(binding) => binding,
+            None => {
+                self.next += 1;
+                self.next - 1
+            }
+        }
+    }
+
+    pub(crate) fn release(&mut self, binding: GLuint) {
+        self.free.push(binding);
+    }
+}
+
+static BINDING_POINTS: Mutex<BindingPoints> = Mutex::new(BindingPoints::new());
+
+// 複数のシェーダープログラムで共有するuniformブロックのバッファー
+// カメラやライトのように毎フレーム1回だけ更新すればよい値をまとめて送る
+pub struct UniformBuffer<T: Std140> {
+    buffer: GLuint,
+    binding: GLuint,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(value: &T) -> UniformBuffer<T> {
+        let mut buffer = 0;
+        let binding = BINDING_POINTS.lock().unwrap().allocate();
+
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            track(GlObjectKind::Buffer, buffer, "UniformBuffer");
+            gl::BindBuffer(gl::UNIFORM_BUFFER, buffer);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                mem::size_of::<T>() as GLsizeiptr,
+                value as *const T as *const c_void,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, buffer);
+        }
+
+        UniformBuffer {
+            buffer,
+            binding,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn update(&self, value: &T) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffer);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                mem::size_of::<T>() as GLsizeiptr,
+                value as *const T as *const c_void,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+}
+
+impl<T: Std140> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            if 0 != self.buffer {
+                untrack(GlObjectKind::Buffer, self.buffer);
+                gl::DeleteBuffers(1, &self.buffer);
+                self.buffer = 0;
+            }
+        }
+        BINDING_POINTS.lock().unwrap().release(self.binding);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UniformBlockError {
+    NotFound {
+        block: String,
+    },
+    SizeMismatch {
+        block: String,
+        expected: usize,
+        actual: usize,
+    },
+    OffsetMismatch {
+        block: String,
+        member: String,
+        expected: usize,
+        actual: usize,
+    },
+    MissingMember {
+        block: String,
+        member: String,
+    },
+    UnknownMember {
+        block: String,
+        member: String,
+    },
+    // ShaderRegistryにそのIDのシェーダーが読み込まれていない
+    ShaderNotLoaded {
+        id: String,
+    },
+}
+
+impl fmt::Display for UniformBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UniformBlockError::NotFound { block } => {
+                write!(f, "uniform block is not active: block={}", block)
+            }
+            UniformBlockError::SizeMismatch {
+                block,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "uniform block size mismatch: block={}, std140={}, rust={}",
+                block, expected, actual
+            ),
+            UniformBlockError::OffsetMismatch {
+                block,
+                member,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "uniform block member offset mismatch: block={}, member={}, std140={}, rust={}",
+                block, member, expected, actual
+            ),
+            UniformBlockError::MissingMember { block, member } => write!(
+                f,
+                "uniform block member is not declared in rust: block={}, member={}",
+                block, member
+            ),
+            UniformBlockError::UnknownMember { block, member } => write!(
+                f,
+                "uniform block member is not found in shader: block={}, member={}",
+                block, member
+            ),
+            UniformBlockError::ShaderNotLoaded { id } => {
+                write!(f, "shader is not loaded: id={}", id)
+            }
+        }
+    }
+}
+
+impl error::Error for UniformBlockError {}
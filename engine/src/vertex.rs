@@ -0,0 +1,651 @@
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+use std::os::raw::c_void;
+use std::ptr;
+use std::slice;
+
+use gl::types::{GLboolean, GLenum, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint};
+
+use crate::gl_object::{track, untrack, GlObjectKind};
+use crate::impl_vertex_layout;
+use crate::vertex_layout::{VertexAttribute, VertexIndex, VertexLayout};
+
+pub struct Vertex {
+    vao: u32,
+    vbo: u32,
+    // 確保済みの頂点バッファーのバイト数
+    vbo_size: usize,
+    usage: GLenum,
+    stride: GLsizei,
+    vertex_num: i32,
+    // インデックスを持つ場合はglDrawElementsで描画する
+    ebo: u32,
+    index_type: GLenum,
+    index_num: i32,
+    primitive: Primitive,
+    // 次に割り当てる頂点属性のlocation
+    next_location: GLuint,
+    // インスタンスごとの属性のバッファー
+    instance_buffers: Vec<InstanceBuffer>,
+}
+
+struct InstanceBuffer {
+    buffer: GLuint,
+    // 確保済みのバイト数
+    size: usize,
+    usage: GLenum,
+    // set_instancesで同じ構造体が渡されたか確かめるために、作ったときの形式を覚えておく
+    stride: usize,
+    attributes: Vec<VertexAttribute>,
+}
+
+impl Vertex {
+    // dataはsizeバイト分読み込める頂点データを指している必要がある
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn new(
+        size: GLsizeiptr,
+        data: *const c_void,
+        usage: GLenum,
+        attribute_type_vec: std::vec::Vec<GLenum>,
+        attribute_size_vec: std::vec::Vec<GLint>,
+        stride: GLsizei,
+        vertex_num: i32,
+    ) -> Vertex {
+        // 属性は隙間なく並んでいるものとして、それぞれの型の大きさからオフセットを求める
+        let mut attributes = Vec::with_capacity(attribute_type_vec.len());
+        let mut offset = 0;
+        for i in 0..attribute_type_vec.len() {
+            let attribute = VertexAttribute {
+                components: attribute_size_vec[i],
+                type_: attribute_type_vec[i],
+                columns: 1,
+                normalized: false,
+                offset,
+            };
+            offset += attribute.byte_size();
+            attributes.push(attribute);
+        }
+
+        unsafe { Vertex::with_attributes(size, data, usage, &attributes, stride, vertex_num) }
+    }
+
+    // 頂点の構造体のスライスから作る
+    // ストライド、各属性のオフセットと型、頂点数はVertexLayoutから求める
+    pub fn from_vertices<V: VertexLayout>(vertices: &[V], usage: GLenum) -> Vertex {
+        unsafe {
+            Vertex::with_attributes(
+                mem::size_of_val(vertices) as GLsizeiptr,
+                vertices.as_ptr() as *const c_void,
+                usage,
+                &V::attributes(),
+                mem::size_of::<V>() as GLsizei,
+                vertices.len() as i32,
+            )
+        }
+    }
+
+    // 正規化する整数の属性や、パディングを挟んだ属性など、各属性の形式を直接指定して作る
+    // dataはsizeバイト分読み込める頂点データを指している必要がある
+    pub unsafe fn with_attributes(
+        size: GLsizeiptr,
+        data: *const c_void,
+        usage: GLenum,
+        attributes: &[VertexAttribute],
+        stride: GLsizei,
+        vertex_num: i32,
+    ) -> Vertex {
+        Vertex::with_buffer_storage(size, usage, attributes, stride, vertex_num, || {
+            gl::BufferData(gl::ARRAY_BUFFER, size, data, usage)
+        })
+    }
+
+    // allocateでバインド中のARRAY_BUFFERの領域を確保する
+    pub(crate) unsafe fn with_buffer_storage<F: FnOnce()>(
+        size: GLsizeiptr,
+        usage: GLenum,
+        attributes: &[VertexAttribute],
+        stride: GLsizei,
+        vertex_num: i32,
+        allocate: F,
+    ) -> Vertex {
+        let mut vao = 0;
+        let mut vbo = 0;
+
+        // create vertex array and vertex buffer
+        gl::GenVertexArrays(1, &mut vao);
+        gl::GenBuffers(1, &mut vbo);
+        track(GlObjectKind::VertexArray, vao, "Vertex");
+        track(GlObjectKind::Buffer, vbo, "Vertex");
+
+        // bind buffer
+        gl::BindVertexArray(vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        allocate();
+
+        let next_location = set_attribute_pointers(attributes, stride, 0, 0);
+
+        // unbind
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::BindVertexArray(0);
+
+        Vertex {
+            vao,
+            vbo,
+            vbo_size: size as usize,
+            usage,
+            stride,
+            vertex_num,
+            ebo: 0,
+            index_type: 0,
+            index_num: 0,
+            primitive: Primitive::Triangles,
+            next_location,
+            instance_buffers: Vec::new(),
+        }
+    }
+
+    // インスタンスごとに変える属性(モデル行列や色など)のバッファーを追加する
+    // 属性のlocationは、頂点の属性(と先に追加したインスタンスの属性)の続きから割り当てる
+    // 例: 頂点がlocation 0〜2を使っていれば、mat4のモデル行列はlocation 3〜6になる
+    pub fn add_instance_buffer<V: VertexLayout>(&mut self, instances: &[V], usage: GLenum) {
+        let mut buffer = 0;
+        let attributes = V::attributes();
+
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            track(GlObjectKind::Buffer, buffer, "Vertex instance buffer");
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(instances) as GLsizeiptr,
+                instances.as_ptr() as *const c_void,
+                usage,
+            );
+
+            // 1インスタンスごとに1つ進める
+            self.next_location = set_attribute_pointers(
+                &attributes,
+                mem::size_of::<V>() as GLsizei,
+                self.next_location,
+                1,
+            );
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+
+        self.instance_buffers.push(InstanceBuffer {
+            buffer,
+            size: mem::size_of_val(instances),
+            usage,
+            stride: mem::size_of::<V>(),
+            attributes,
+        });
+    }
+
+    // 頂点のrangeの範囲をverticesで書き換える
+    // 確保済みの領域を超える場合はset_verticesを使う
+    pub fn update<V: VertexLayout>(&self, range: Range<usize>, vertices: &[V]) {
+        self.check_stride::<V>();
+        assert_eq!(
+            range.len(),
+            vertices.len(),
+            "vertex update range and data length differ"
+        );
+        let offset = range.start * mem::size_of::<V>();
+        assert!(
+            offset + mem::size_of_val(vertices) <= self.vbo_size,
+            "vertex update is out of range: range={:?}, capacity={}",
+            range,
+            self.vbo_size / mem::size_of::<V>()
+        );
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                offset as GLintptr,
+                mem::size_of_val(vertices) as GLsizeiptr,
+                vertices.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // 頂点データを丸ごと入れ替える(頂点数が変わってもよい)
+    // 毎フレーム書き換えるメッシュ向けに、GPUが前のデータを使い終わるのを待たないように
+    // 同じ大きさの領域を確保し直して(orphaning)から書き込む
+    pub fn set_vertices<V: VertexLayout>(&mut self, vertices: &[V]) {
+        self.check_stride::<V>();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            self.vbo_size = orphan_and_write(self.vbo_size, vertices, self.usage);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.vertex_num = vertices.len() as i32;
+    }
+
+    // add_instance_bufferで追加したindex番目のバッファーの内容を入れ替える
+    // 追加したときと同じ構造体を渡すこと
+    pub fn set_instances<V: VertexLayout>(&mut self, index: usize, instances: &[V]) {
+        let instance_buffer = &mut self.instance_buffers[index];
+        assert!(
+            mem::size_of::<V>() == instance_buffer.stride
+                && V::attributes() == instance_buffer.attributes,
+            "instance type does not match the layout of this buffer"
+        );
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, instance_buffer.buffer);
+            instance_buffer.size =
+                orphan_and_write(instance_buffer.size, instances, instance_buffer.usage);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    fn check_stride<V>(&self) {
+        assert_eq!(
+            mem::size_of::<V>(),
+            self.stride as usize,
+            "vertex type does not match the stride of this buffer"
+        );
+    }
+
+    // 頂点の構造体とインデックス(u16かu32)から、インデックス付きの頂点データを作る
+    // 重複した頂点を含むデータはdeduplicate_verticesで変換できる
+    pub fn from_indexed_vertices<V: VertexLayout, I: VertexIndex>(
+        vertices: &[V],
+        indices: &[I],
+        usage: GLenum,
+    ) -> Vertex {
+        let mut vertex = Vertex::from_vertices(vertices, usage);
+
+        unsafe {
+            gl::GenBuffers(1, &mut vertex.ebo);
+            track(GlObjectKind::Buffer, vertex.ebo, "Vertex index buffer");
+
+            // element array bufferの割り当てはVAOに記録される
+            gl::BindVertexArray(vertex.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vertex.ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                mem::size_of_val(indices) as GLsizeiptr,
+                indices.as_ptr() as *const c_void,
+                usage,
+            );
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        vertex.index_type = I::TYPE;
+        vertex.index_num = indices.len() as i32;
+        vertex
+    }
+
+    pub fn draw(&self) {
+        self.draw_range(0, self.element_num());
+    }
+
+    // first番目からcount個の頂点(インデックスを持つ場合はインデックス)を描画する
+    // 1つのバッファーに複数のメッシュを詰めて、部分ごとに描画するときに使う
+    pub fn draw_range(&self, first: i32, count: i32) {
+        self.draw_primitive(self.primitive, first, count);
+    }
+
+    // テッセレーションシェーダー用に、vertices_per_patch個ずつの頂点をパッチとして描画する
+    // new_screen_vertex_vecの頂点データは3頂点ずつの三角形のパッチになる
+    pub fn draw_patches(&self, vertices_per_patch: i32) {
+        self.draw_primitive(
+            Primitive::Patches(vertices_per_patch),
+            0,
+            self.element_num(),
+        );
+    }
+
+    fn draw_primitive(&self, primitive: Primitive, first: i32, count: i32) {
+        unsafe {
+            let mode = self.begin_draw(primitive);
+            if 0 != self.ebo {
+                let offset = first as usize * index_size(self.index_type);
+                gl::DrawElements(mode, count, self.index_type, offset as *const c_void);
+            } else {
+                gl::DrawArrays(mode, first, count);
+            }
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // 同じ形状をinstance_count個まとめて描画する
+    // シェーダーではgl_InstanceIDかadd_instance_bufferで追加した属性でインスタンスを区別する
+    pub fn draw_instanced(&self, instance_count: i32) {
+        unsafe {
+            let mode = self.begin_draw(self.primitive);
+            if 0 != self.ebo {
+                gl::DrawElementsInstanced(
+                    mode,
+                    self.index_num,
+                    self.index_type,
+                    ptr::null(),
+                    instance_count,
+                );
+            } else {
+                gl::DrawArraysInstanced(mode, 0, self.vertex_num, instance_count);
+            }
+            gl::BindVertexArray(0);
+        }
+    }
+
+    pub fn primitive(&self) -> Primitive {
+        self.primitive
+    }
+
+    // 描画する図形の種類を変える(初期値は三角形)
+    pub fn set_primitive(&mut self, primitive: Primitive) {
+        self.primitive = primitive;
+    }
+
+    // 描画する頂点(インデックスを持つ場合はインデックス)の数
+    pub fn element_num(&self) -> i32 {
+        if 0 != self.ebo {
+            self.index_num
+        } else {
+            self.vertex_num
+        }
+    }
+
+    pub(crate) fn vbo(&self) -> GLuint {
+        self.vbo
+    }
+
+    // VAOをバインドし、図形の種類に必要な設定をしてモードを返す
+    unsafe fn begin_draw(&self, primitive: Primitive) -> GLenum {
+        match primitive {
+            // 頂点シェーダーのgl_PointSizeで点の大きさを変えられるようにする
+            Primitive::Points => gl::Enable(gl::PROGRAM_POINT_SIZE),
+            Primitive::Patches(vertices_per_patch) => {
+                gl::PatchParameteri(gl::PATCH_VERTICES, vertices_per_patch)
+            }
+            _ => (),
+        }
+        gl::BindVertexArray(self.vao);
+        primitive.gl_enum()
+    }
+}
+
+impl Drop for Vertex {
+    fn drop(&mut self) {
+        unsafe {
+            for instance_buffer in self.instance_buffers.drain(..) {
+                untrack(GlObjectKind::Buffer, instance_buffer.buffer);
+                gl::DeleteBuffers(1, &instance_buffer.buffer);
+            }
+            if 0 != self.ebo {
+                untrack(GlObjectKind::Buffer, self.ebo);
+                gl::DeleteBuffers(1, &self.ebo);
+                self.ebo = 0;
+            }
+            if 0 != self.vbo {
+                untrack(GlObjectKind::Buffer, self.vbo);
+                gl::DeleteBuffers(1, &self.vbo);
+                self.vbo = 0;
+            }
+            if 0 != self.vao {
+                untrack(GlObjectKind::VertexArray, self.vao);
+                gl::DeleteVertexArrays(1, &self.vao);
+                self.vao = 0;
+            }
+        }
+    }
+}
+
+// 頂点をどのような図形として描画するか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    // テッセレーションシェーダーに渡すパッチ(1パッチあたりの頂点数)
+    Patches(i32),
+}
+
+impl Primitive {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            Primitive::Points => gl::POINTS,
+            Primitive::Lines => gl::LINES,
+            Primitive::LineStrip => gl::LINE_STRIP,
+            Primitive::LineLoop => gl::LINE_LOOP,
+            Primitive::Triangles => gl::TRIANGLES,
+            Primitive::TriangleStrip => gl::TRIANGLE_STRIP,
+            Primitive::TriangleFan => gl::TRIANGLE_FAN,
+            Primitive::Patches(_) => gl::PATCHES,
+        }
+    }
+}
+
+fn index_size(index_type: GLenum) -> usize {
+    match index_type {
+        gl::UNSIGNED_SHORT => mem::size_of::<u16>(),
+        _ => mem::size_of::<u32>(),
+    }
+}
+
+// バインド中のARRAY_BUFFERにdataを書き込み、確保済みのバイト数を返す
+// 収まるなら同じ大きさで確保し直してから書き込み、収まらなければ大きく確保し直す
+unsafe fn orphan_and_write<T>(capacity: usize, data: &[T], usage: GLenum) -> usize {
+    let size = mem::size_of_val(data);
+    if size > capacity {
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            size as GLsizeiptr,
+            data.as_ptr() as *const c_void,
+            usage,
+        );
+        size
+    } else {
+        gl::BufferData(gl::ARRAY_BUFFER, capacity as GLsizeiptr, ptr::null(), usage);
+        gl::BufferSubData(
+            gl::ARRAY_BUFFER,
+            0,
+            size as GLsizeiptr,
+            data.as_ptr() as *const c_void,
+        );
+        capacity
+    }
+}
+
+// バインド中のARRAY_BUFFERに対して、first_locationから順に頂点属性を設定する
+// divisorが0なら頂点ごと、1ならインスタンスごとに読み進める
+// 次に使えるlocationを返す
+unsafe fn set_attribute_pointers(
+    attributes: &[VertexAttribute],
+    stride: GLsizei,
+    first_location: GLuint,
+    divisor: GLuint,
+) -> GLuint {
+    let mut location = first_location;
+    for attribute in attributes {
+        for column in 0..attribute.columns as usize {
+            let offset = (attribute.offset + attribute.column_size() * column) as *const c_void;
+            gl::EnableVertexAttribArray(location);
+            if attribute.is_integer() {
+                gl::VertexAttribIPointer(
+                    location,
+                    attribute.components,
+                    attribute.type_,
+                    stride,
+                    offset,
+                );
+            } else {
+                gl::VertexAttribPointer(
+                    location,
+                    attribute.components,
+                    attribute.type_,
+                    attribute.normalized as GLboolean,
+                    stride,
+                    offset,
+                );
+            }
+            if 0 != divisor {
+                gl::VertexAttribDivisor(location, divisor);
+            }
+            location += 1;
+        }
+    }
+    location
+}
+
+// インデックスを持たない頂点データから重複した頂点を取り除き、頂点とインデックスに分ける
+// 頂点属性のバイト列が完全に一致するものを同じ頂点とみなす(パディングは比較しない)
+// インデックスの型はu16かu32で、小さなメッシュはu16にするとインデックスのバッファーが半分になる
+// 重複を取り除いた頂点の数がインデックスの型に収まらなければNoneを返す
+pub fn deduplicate_vertices<V: VertexLayout, I: VertexIndex>(
+    vertices: &[V],
+) -> Option<(Vec<V>, Vec<I>)> {
+    let attributes = V::attributes();
+    let key = |vertex: &V| -> Vec<u8> {
+        let bytes = vertex as *const V as *const u8;
+        let mut key = Vec::with_capacity(mem::size_of::<V>());
+        for attribute in &attributes {
+            let field = unsafe {
+                slice::from_raw_parts(bytes.add(attribute.offset), attribute.byte_size())
+            };
+            key.extend_from_slice(field);
+        }
+        key
+    };
+
+    let mut unique_vertices = Vec::new();
+    let mut indices = Vec::with_capacity(vertices.len());
+    let mut index_map: HashMap<Vec<u8>, I> = HashMap::new();
+    for vertex in vertices {
+        let vertex_key = key(vertex);
+        let index = match index_map.get(&vertex_key) {
+            Some(&index) => index,
+            None => {
+                let index = I::from_index(unique_vertices.len())?;
+                unique_vertices.push(*vertex);
+                index_map.insert(vertex_key, index);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    Some((unique_vertices, indices))
+}
+
+// 画面用の頂点
+// 三次元座標とテクスチャ上の二次元座標を持つ
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+
+impl_vertex_layout!(ScreenVertex {
+    position,
+    tex_coords
+});
+
+/// 四角形を分割した画面用の頂点データを生成する
+///
+/// 1つの頂点につき、三次元座標とテクスチャ上の二次元座標を持つ
+pub fn new_screen_vertex_vec(
+    // 生成したい四角形の座標
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+    // 分割数
+    // ! 分割数を1にすると三角形2個で構成された頂点データになる
+    // ! 5を指定すると三角形5o個、10を指定すると200個
+    // * 引数nの時、三角形の数: 2n^2, 頂点の数: 6n^2
+    division: i32,
+) -> std::vec::Vec<ScreenVertex> {
+    let mut vertex_vec: std::vec::Vec<ScreenVertex> = std::vec::Vec::new();
+
+    for x in 0..division {
+        for y in 0..division {
+            let l = left + (right - left) / division as f32 * x as f32;
+            let r = left + (right - left) / division as f32 * (x + 1) as f32;
+            let t = top + (bottom - top) / division as f32 * y as f32;
+            let b = top + (bottom - top) / division as f32 * (y + 1) as f32;
+
+            let lc = 1.0 / division as f32 * x as f32;
+            let rc = 1.0 / division as f32 * (x + 1) as f32;
+            let tc = 1.0 / division as f32 * y as f32;
+            let bc = 1.0 / division as f32 * (y + 1) as f32;
+
+            let vertex = |x, y, u, v| ScreenVertex {
+                position: [x, y, 0.0],
+                tex_coords: [u, v],
+            };
+            vertex_vec.push(vertex(l, t, lc, tc));
+            vertex_vec.push(vertex(r, t, rc, tc));
+            vertex_vec.push(vertex(l, b, lc, bc));
+            vertex_vec.push(vertex(l, b, lc, bc));
+            vertex_vec.push(vertex(r, t, rc, tc));
+            vertex_vec.push(vertex(r, b, rc, bc));
+        }
+    }
+
+    vertex_vec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32) -> ScreenVertex {
+        ScreenVertex {
+            position: [x, 0.0, 0.0],
+            tex_coords: [0.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn deduplicate_keeps_first_occurrence_order() {
+        let vertices = [
+            vertex(2.0),
+            vertex(1.0),
+            vertex(2.0),
+            vertex(3.0),
+            vertex(1.0),
+        ];
+        let (unique, indices) = deduplicate_vertices::<_, u16>(&vertices).unwrap();
+        assert_eq!(unique, vec![vertex(2.0), vertex(1.0), vertex(3.0)]);
+        assert_eq!(indices, vec![0, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn deduplicate_screen_quad() {
+        // 1分割の四角形は6頂点のうち2つが重複している
+        let vertices = new_screen_vertex_vec(-1.0, 1.0, 1.0, -1.0, 1);
+        let (unique, indices) = deduplicate_vertices::<_, u32>(&vertices).unwrap();
+        assert_eq!(unique.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 3]);
+        for (vertex, &index) in vertices.iter().zip(indices.iter()) {
+            assert_eq!(*vertex, unique[index as usize]);
+        }
+    }
+
+    #[test]
+    fn deduplicate_returns_none_when_u16_overflows() {
+        // u16で表せるのは65536頂点まで
+        let vertices: Vec<ScreenVertex> = (0..=u16::MAX as u32).map(|i| vertex(i as f32)).collect();
+        let (unique, indices) = deduplicate_vertices::<_, u16>(&vertices).unwrap();
+        assert_eq!(unique.len(), 65536);
+        assert_eq!(indices.last(), Some(&u16::MAX));
+
+        let vertices: Vec<ScreenVertex> = (0..=65536).map(|i| vertex(i as f32)).collect();
+        assert!(deduplicate_vertices::<_, u16>(&vertices).is_none());
+        assert!(deduplicate_vertices::<_, u32>(&vertices).is_some());
+    }
+}
@@ -0,0 +1,221 @@
+use std::convert::TryFrom;
+use std::mem;
+
+use gl::types::{GLbyte, GLenum, GLfloat, GLint, GLshort, GLubyte, GLuint, GLushort};
+
+// 頂点属性1つ分の形式
+// locationは構造体のフィールドの順番(0から)になる
+// 行列は列ごとに1つずつ、連続したlocationを使う(mat4なら4つ)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub components: GLint,
+    pub type_: GLenum,
+    pub columns: GLint,
+    // 整数の型を0.0〜1.0(符号付きなら-1.0〜1.0)に正規化してfloatとして読むかどうか
+    // 整数の型で正規化しない場合は、シェーダー側でもint/uint(ivec4など)として受け取る
+    pub normalized: bool,
+    // 頂点の先頭からのバイトオフセット
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    // fieldはフィールドの型を推論させるためだけに使う
+    pub fn of<V, T: AttributeFormat>(offset: usize, _field: fn(&V) -> &T) -> VertexAttribute {
+        assert!(
+            is_packed_type(T::TYPE) || gl_type_size(T::TYPE).is_some(),
+            "unsupported vertex attribute type: 0x{:x}",
+            T::TYPE
+        );
+        VertexAttribute {
+            components: T::COMPONENTS,
+            type_: T::TYPE,
+            columns: T::COLUMNS,
+            normalized: T::NORMALIZED,
+            offset,
+        }
+    }
+
+    // この属性が頂点の中で占めるバイト数
+    pub fn byte_size(&self) -> usize {
+        self.column_size() * self.columns as usize
+    }
+
+    // 行列の1列分のバイト数
+    // 知らない型は、以前のVertex::newと同じく1要素4バイトとみなす
+    pub fn column_size(&self) -> usize {
+        if is_packed_type(self.type_) {
+            return mem::size_of::<GLuint>();
+        }
+        gl_type_size(self.type_).unwrap_or(mem::size_of::<GLfloat>()) * self.components as usize
+    }
+
+    // VertexAttribIPointerで整数のまま渡す属性かどうか
+    pub fn is_integer(&self) -> bool {
+        !self.normalized && is_integer_type(self.type_)
+    }
+}
+
+// 1要素のバイト数(知らない型ならNone)
+pub(crate) fn gl_type_size(type_: GLenum) -> Option<usize> {
+    match type_ {
+        gl::BYTE => Some(mem::size_of::<GLbyte>()),
+        gl::UNSIGNED_BYTE => Some(mem::size_of::<GLubyte>()),
+        gl::SHORT => Some(mem::size_of::<GLshort>()),
+        gl::UNSIGNED_SHORT | gl::HALF_FLOAT => Some(mem::size_of::<GLushort>()),
+        gl::INT => Some(mem::size_of::<GLint>()),
+        gl::UNSIGNED_INT => Some(mem::size_of::<GLuint>()),
+        gl::FLOAT => Some(mem::size_of::<GLfloat>()),
+        gl::DOUBLE => Some(mem::size_of::<f64>()),
+        _ => None,
+    }
+}
+
+// 全ての要素を4バイトに詰めた型
+fn is_packed_type(type_: GLenum) -> bool {
+    matches!(
+        type_,
+        gl::INT_2_10_10_10_REV | gl::UNSIGNED_INT_2_10_10_10_REV | gl::UNSIGNED_INT_10F_11F_11F_REV
+    )
+}
+
+fn is_integer_type(type_: GLenum) -> bool {
+    matches!(
+        type_,
+        gl::BYTE | gl::UNSIGNED_BYTE | gl::SHORT | gl::UNSIGNED_SHORT | gl::INT | gl::UNSIGNED_INT
+    )
+}
+
+// 頂点属性のフィールドに使える型
+// 実装されていない型をフィールドにするとimpl_vertex_layout!がコンパイルエラーになる
+pub unsafe trait AttributeFormat {
+    const COMPONENTS: GLint;
+    const TYPE: GLenum;
+    const COLUMNS: GLint = 1;
+    const NORMALIZED: bool = false;
+}
+
+// 整数の頂点属性を正規化してfloatとして読ませる
+// 例: 色を Normalized([255u8, 128, 0, 255]) のように4バイトで持つ
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalized<T>(pub T);
+
+macro_rules! impl_attribute_format {
+    ($type_:ty, $components:expr, $gl_type:expr) => {
+        unsafe impl AttributeFormat for $type_ {
+            const COMPONENTS: GLint = $components;
+            const TYPE: GLenum = $gl_type;
+        }
+    };
+}
+
+// スカラーと、要素数1〜4の配列をまとめて実装する
+// 正規化できる整数の型は、Normalizedで包んだものも実装する
+macro_rules! impl_attribute_formats {
+    ($scalar:ty, $gl_type:expr) => {
+        impl_attribute_format!($scalar, 1, $gl_type);
+        impl_attribute_format!([$scalar; 1], 1, $gl_type);
+        impl_attribute_format!([$scalar; 2], 2, $gl_type);
+        impl_attribute_format!([$scalar; 3], 3, $gl_type);
+        impl_attribute_format!([$scalar; 4], 4, $gl_type);
+    };
+    ($scalar:ty, $gl_type:expr, normalized) => {
+        impl_attribute_formats!($scalar, $gl_type);
+        impl_attribute_formats!(@normalized $scalar, 1, $gl_type);
+        impl_attribute_formats!(@normalized [$scalar; 1], 1, $gl_type);
+        impl_attribute_formats!(@normalized [$scalar; 2], 2, $gl_type);
+        impl_attribute_formats!(@normalized [$scalar; 3], 3, $gl_type);
+        impl_attribute_formats!(@normalized [$scalar; 4], 4, $gl_type);
+    };
+    (@normalized $type_:ty, $components:expr, $gl_type:expr) => {
+        unsafe impl AttributeFormat for Normalized<$type_> {
+            const COMPONENTS: GLint = $components;
+            const TYPE: GLenum = $gl_type;
+            const NORMALIZED: bool = true;
+        }
+    };
+}
+
+impl_attribute_formats!(f32, gl::FLOAT);
+impl_attribute_formats!(half::f16, gl::HALF_FLOAT);
+impl_attribute_formats!(i8, gl::BYTE, normalized);
+impl_attribute_formats!(u8, gl::UNSIGNED_BYTE, normalized);
+impl_attribute_formats!(i16, gl::SHORT, normalized);
+impl_attribute_formats!(u16, gl::UNSIGNED_SHORT, normalized);
+impl_attribute_formats!(i32, gl::INT);
+impl_attribute_formats!(u32, gl::UNSIGNED_INT);
+impl_attribute_format!(cgmath::Vector2<f32>, 2, gl::FLOAT);
+impl_attribute_format!(cgmath::Vector3<f32>, 3, gl::FLOAT);
+impl_attribute_format!(cgmath::Vector4<f32>, 4, gl::FLOAT);
+impl_attribute_format!(cgmath::Point2<f32>, 2, gl::FLOAT);
+impl_attribute_format!(cgmath::Point3<f32>, 3, gl::FLOAT);
+
+// インスタンスごとのモデル行列などに使う
+macro_rules! impl_attribute_format_matrix {
+    ($type_:ty, $size:expr) => {
+        unsafe impl AttributeFormat for $type_ {
+            const COMPONENTS: GLint = $size;
+            const TYPE: GLenum = gl::FLOAT;
+            const COLUMNS: GLint = $size;
+        }
+    };
+}
+
+impl_attribute_format_matrix!(cgmath::Matrix2<f32>, 2);
+impl_attribute_format_matrix!(cgmath::Matrix3<f32>, 3);
+impl_attribute_format_matrix!(cgmath::Matrix4<f32>, 4);
+impl_attribute_format_matrix!([[f32; 2]; 2], 2);
+impl_attribute_format_matrix!([[f32; 3]; 3], 3);
+impl_attribute_format_matrix!([[f32; 4]; 4], 4);
+
+// glDrawElementsで使うインデックスの型
+pub unsafe trait VertexIndex: Copy {
+    const TYPE: GLenum;
+
+    // この型に収まらない番号ならNoneを返す
+    fn from_index(index: usize) -> Option<Self>;
+}
+
+unsafe impl VertexIndex for u16 {
+    const TYPE: GLenum = gl::UNSIGNED_SHORT;
+
+    fn from_index(index: usize) -> Option<Self> {
+        u16::try_from(index).ok()
+    }
+}
+
+unsafe impl VertexIndex for u32 {
+    const TYPE: GLenum = gl::UNSIGNED_INT;
+
+    fn from_index(index: usize) -> Option<Self> {
+        u32::try_from(index).ok()
+    }
+}
+
+// Vertex::from_verticesでそのままGPUへ送ることのできる頂点の構造体
+// #[repr(C)]で定義し、impl_vertex_layout!マクロで実装するとよい
+pub unsafe trait VertexLayout: Copy {
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+// 例: impl_vertex_layout!(ScreenVertex { position, tex_coords });
+// フィールドは漏れなく並べること(漏れがあるとコンパイルエラーになる)
+// 並べた順番が頂点属性のlocationになる
+#[macro_export]
+macro_rules! impl_vertex_layout {
+    ($type_:ident { $($field:ident),* $(,)? }) => {
+        unsafe impl $crate::VertexLayout for $type_ {
+            fn attributes() -> Vec<$crate::VertexAttribute> {
+                #[allow(dead_code)]
+                fn all_fields_listed(vertex: &$type_) {
+                    let $type_ { $($field: _),* } = vertex;
+                }
+
+                vec![$($crate::VertexAttribute::of(
+                    ::std::mem::offset_of!($type_, $field),
+                    |vertex: &$type_| &vertex.$field,
+                )),*]
+            }
+        }
+    };
+}